@@ -1,7 +1,7 @@
 use preferences::{AppInfo, Preferences};
 use ruspiro_singleton::Singleton;
 
-use crate::melee::SlippiMenuScene;
+use crate::melee::{MeleeScene, SlippiMenuScene};
 
 pub const APP_INFO: AppInfo = AppInfo {
     name: "conf",
@@ -16,12 +16,30 @@ pub static CONFIG: Singleton<AppConfig> = Singleton::lazy(&|| {
     }
 });
 
+// which Dolphin we read from if there are multiple running, a pinned executable always wins
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub enum DolphinSelection {
+    PreferNetplay,
+    PreferInGame
+}
+
 structstruck::strike! {
     #[strikethrough[derive(Serialize, Deserialize, PartialEq, Debug)]]
     pub struct AppConfig {
         pub global: struct {
             pub show_in_game_character: bool,
-            pub show_in_game_time: bool
+            pub show_in_game_time: bool,
+            #[serde(default = "enabled")]
+            pub show_session: bool,
+            #[serde(default = "enabled")]
+            pub show_stocks: bool,
+            #[serde(default = "enabled")]
+            pub show_win_loss: bool
+        },
+        #[serde(default)]
+        pub dolphin: struct {
+            pub selection: DolphinSelection,
+            pub pinned_path: Option<String>
         },
         pub slippi: struct {
             pub enabled: bool,
@@ -31,7 +49,9 @@ structstruck::strike! {
                 pub enabled: bool,
                 pub show_rank: bool,
                 pub show_view_ranked_profile_button: bool,
-                pub show_score: bool
+                pub show_score: bool,
+                #[serde(default = "enabled")]
+                pub show_stage_striking: bool
             },
             pub unranked: struct {
                 pub enabled: bool
@@ -47,21 +67,53 @@ structstruck::strike! {
             pub enabled: bool
         },
         pub vs_mode: struct {
-            pub enabled: bool
+            pub enabled: bool,
+            #[serde(default = "enabled")]
+            pub show_rules: bool
         },
         pub training_mode: struct {
-            pub enabled: bool
+            pub enabled: bool,
+            #[serde(default = "enabled")]
+            pub show_partner: bool
         },
         pub stadium: struct {
             pub enabled: bool,
             pub hrc: struct {
-                pub enabled: bool
+                pub enabled: bool,
+                #[serde(default = "enabled")]
+                pub show_record: bool,
+                #[serde(default = "enabled")]
+                pub show_progress: bool
             },
             pub btt: struct {
                 pub enabled: bool,
-                pub show_stage_name: bool
+                pub show_stage_name: bool,
+                #[serde(default = "enabled")]
+                pub show_record: bool,
+                #[serde(default = "enabled")]
+                pub show_progress: bool
             },
             pub mmm: struct {
+                pub enabled: bool,
+                #[serde(default = "enabled")]
+                pub show_record: bool,
+                #[serde(default = "enabled")]
+                pub show_progress: bool
+            }
+        },
+        #[serde(default)]
+        pub one_player: struct {
+            pub enabled: bool,
+            pub classic: struct {
+                pub enabled: bool
+            },
+            pub adventure: struct {
+                pub enabled: bool
+            },
+            pub all_star: struct {
+                pub enabled: bool
+            },
+            pub event_match: struct {
                 pub enabled: bool
             }
         }
@@ -73,8 +125,12 @@ impl Default for AppConfig {
         AppConfig {
             global: Global {
                 show_in_game_character: true,
-                show_in_game_time: true
+                show_in_game_time: true,
+                show_session: true,
+                show_stocks: true,
+                show_win_loss: true
             },
+            dolphin: Dolphin::default(),
             slippi: Slippi {
                 enabled: true,
                 show_queueing: true,
@@ -83,32 +139,72 @@ impl Default for AppConfig {
                     enabled: true,
                     show_rank: true,
                     show_view_ranked_profile_button: true,
-                    show_score: true
+                    show_score: true,
+                    show_stage_striking: true
                 },
                 unranked: Unranked { enabled: true },
                 direct: Direct { enabled: true },
                 teams: Teams { enabled: true }
             },
             uncle_punch: UnclePunch { enabled: true },
-            vs_mode: VsMode { enabled: true },
-            training_mode: TrainingMode { enabled: true },
+            vs_mode: VsMode { enabled: true, show_rules: true },
+            training_mode: TrainingMode { enabled: true, show_partner: true },
             stadium: Stadium {
                 enabled: true,
                 hrc: Hrc {
-                    enabled: true
+                    enabled: true,
+                    show_record: true,
+                    show_progress: true
                 },
                 btt: Btt {
                     enabled: true,
-                    show_stage_name: true
+                    show_stage_name: true,
+                    show_record: true,
+                    show_progress: true
                 },
                 mmm: Mmm {
-                    enabled: true
+                    enabled: true,
+                    show_record: true,
+                    show_progress: true
                 }
+            },
+            one_player: OnePlayer::default()
+        }
+    }
+}
+
+impl Default for OnePlayer {
+    fn default() -> Self {
+        OnePlayer {
+            enabled: true,
+            classic: Classic {
+                enabled: true
+            },
+            adventure: Adventure {
+                enabled: true
+            },
+            all_star: AllStar {
+                enabled: true
+            },
+            event_match: EventMatch {
+                enabled: true
             }
         }
     }
 }
 
+impl Default for Dolphin {
+    fn default() -> Self {
+        Dolphin {
+            selection: DolphinSelection::PreferNetplay,
+            pinned_path: None
+        }
+    }
+}
+
+// serde default for options that are on by default, so existing configs keep loading
+fn enabled() -> bool { true }
+
 pub fn write_config(val: &AppConfig) {
     let _ = val.save(&APP_INFO, PREFS_KEY);
 }
@@ -123,4 +219,24 @@ impl SlippiMenuScene {
             SlippiMenuScene::Teams => c.slippi.teams.enabled
         }
     }
-}
\ No newline at end of file
+}
+
+impl MeleeScene {
+    pub fn is_enabled(&self, c: &AppConfig) -> bool {
+        match *self {
+            MeleeScene::SlippiOnline(scene) | MeleeScene::SlippiCss(scene) => c.slippi.enabled &&
+                scene.and_then(|s| Some(s.is_enabled(c))).unwrap_or(true),
+            MeleeScene::StageStriking => c.slippi.enabled && c.slippi.ranked.enabled,
+            MeleeScene::UnclePunch => c.uncle_punch.enabled,
+            MeleeScene::TrainingMode => c.training_mode.enabled,
+            MeleeScene::VsMode | MeleeScene::SpecialMelee(_) | MeleeScene::TournamentMelee => c.vs_mode.enabled,
+            MeleeScene::HomeRunContest => c.stadium.enabled && c.stadium.hrc.enabled,
+            MeleeScene::TargetTest(_) => c.stadium.enabled && c.stadium.btt.enabled,
+            MeleeScene::MultiManMelee(_) => c.stadium.enabled && c.stadium.mmm.enabled,
+            MeleeScene::Classic => c.one_player.enabled && c.one_player.classic.enabled,
+            MeleeScene::Adventure => c.one_player.enabled && c.one_player.adventure.enabled,
+            MeleeScene::AllStar => c.one_player.enabled && c.one_player.all_star.enabled,
+            MeleeScene::EventMatch => c.one_player.enabled && c.one_player.event_match.enabled
+        }
+    }
+}
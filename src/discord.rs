@@ -1,6 +1,6 @@
-use discord_rich_presence::{activity::{self, Timestamps, Button}, DiscordIpc, DiscordIpcClient};
+use discord_rich_presence::{activity::{self, Timestamps, Button, Party}, DiscordIpc, DiscordIpcClient};
 
-use crate::{util::current_unix_time, melee::{stage::{MeleeStage, OptionalMeleeStage}, character::{MeleeCharacter, OptionalMeleeCharacter}, MeleeScene, SlippiMenuScene, dolphin_user::get_connect_code}, rank, config::CONFIG};
+use crate::{util::current_unix_time, melee::{stage::{MeleeStage, OptionalMeleeStage}, character::{MeleeCharacter, OptionalMeleeCharacter}, MeleeScene, SlippiMenuScene, RankedSetScore, RecordStatus, StadiumProgress, Matchup, StockStatus, TeamNames, WinLoss, MatchRules, PlayerCharacter, dolphin_user::get_connect_code}, rank, config::CONFIG};
 use crate::util;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -8,6 +8,8 @@ pub enum DiscordClientRequestType {
     Clear,
     Queue,
     Game,
+    Session,
+    StageStriking,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -31,7 +33,7 @@ impl DiscordClientRequestTimestamp {
 // we ignore this field
 impl PartialEq for DiscordClientRequestTimestamp {
     fn eq(&self, o: &Self) -> bool {
-        // if the game was in pause for too long, resynchronize by saying that this payload is not the same as the other.
+        // pauses we detect hide the timestamp and resynchronize it on resume, this catches everything else that makes it drift (e.g. lag).
         // To respect the rate limit, we choose a relatively high amount of seconds
         self.mode == DiscordClientRequestTimestampMode::Static || self.timestamp.abs_diff(o.timestamp) < 15
     }
@@ -45,7 +47,20 @@ pub struct DiscordClientRequest {
     pub character: OptionalMeleeCharacter,
     pub mode: String,
     pub timestamp: DiscordClientRequestTimestamp,
-    pub opp_name: Option<String>
+    pub opp_name: Option<String>,
+    pub team_names: Option<TeamNames>,
+    pub score: Option<RankedSetScore>,
+    pub record: Option<RecordStatus>,
+    pub progress: Option<StadiumProgress>,
+    pub rules: Option<MatchRules>,
+    pub partner: Option<PlayerCharacter>, // training mode
+    pub paused: bool,
+    pub paused_clock: Option<u32>, // seconds on the game clock when it was paused
+    pub matchup: Option<Matchup>,
+    pub stocks: Option<StockStatus>,
+    pub party_size: Option<(u8, u8)>, // players, max players
+    pub games_played: u32,
+    pub win_loss: Option<WinLoss> // of the session
 }
 
 impl Default for DiscordClientRequest {
@@ -54,38 +69,77 @@ impl Default for DiscordClientRequest {
             req_type: DiscordClientRequestType::Clear,
             scene: None,
             stage: OptionalMeleeStage(None),
-            character: OptionalMeleeCharacter(None),
+            character: OptionalMeleeCharacter(None, None),
             mode: "".into(),
             timestamp: DiscordClientRequestTimestamp {
                 mode: DiscordClientRequestTimestampMode::Static,
                 timestamp: current_unix_time(),
             },
-            opp_name: None
+            opp_name: None,
+            team_names: None,
+            score: None,
+            record: None,
+            progress: None,
+            rules: None,
+            partner: None,
+            paused: false,
+            paused_clock: None,
+            matchup: None,
+            stocks: None,
+            party_size: None,
+            games_played: 0,
+            win_loss: None
         }
     }
 }
 
 impl DiscordClientRequest {
     pub fn clear() -> Self { Default::default() }
-    pub fn queue(scene: Option<SlippiMenuScene>, character: Option<MeleeCharacter>) -> Self {
+    pub fn queue(scene: Option<SlippiMenuScene>, character: Option<MeleeCharacter>, costume: Option<u8>) -> Self {
         Self {
             req_type: DiscordClientRequestType::Queue,
             scene,
-            character: OptionalMeleeCharacter(character),
+            character: OptionalMeleeCharacter(character, costume),
             ..Default::default()
         }
     }
-    pub fn game(stage: Option<MeleeStage>, character: Option<MeleeCharacter>, mode: MeleeScene, timestamp: DiscordClientRequestTimestamp, opp_name: Option<String>) -> Self {
+    // everything else a game shows is filled in with struct update syntax
+    pub fn game(stage: Option<MeleeStage>, character: Option<MeleeCharacter>, costume: Option<u8>, mode: MeleeScene, timestamp: DiscordClientRequestTimestamp) -> Self {
         Self {
             req_type: DiscordClientRequestType::Game,
             stage: OptionalMeleeStage(stage),
-            character: OptionalMeleeCharacter(character),
+            character: OptionalMeleeCharacter(character, costume),
             mode: mode.to_string(),
             timestamp,
+            ..Default::default()
+        }
+    }
+    // stadium progress and stocks/percents change all the time during a game
+    pub fn only_live_state_differs(&self, o: &Self) -> bool {
+        (self.progress != o.progress || self.stocks != o.stocks) && Self { progress: o.progress, stocks: o.stocks.clone(), ..self.clone() } == *o
+    }
+    pub fn stage_striking(stage: Option<MeleeStage>, opp_name: Option<String>) -> Self {
+        Self {
+            req_type: DiscordClientRequestType::StageStriking,
+            scene: Some(SlippiMenuScene::Ranked),
+            stage: OptionalMeleeStage(stage),
             opp_name,
             ..Default::default()
         }
     }
+    pub fn session(mode: MeleeScene, start: i64, games_played: u32, win_loss: Option<WinLoss>) -> Self {
+        Self {
+            req_type: DiscordClientRequestType::Session,
+            mode: mode.to_string(),
+            timestamp: DiscordClientRequestTimestamp {
+                mode: DiscordClientRequestTimestampMode::Static,
+                timestamp: start
+            },
+            games_played,
+            win_loss,
+            ..Default::default()
+        }
+    }
 }
 
 pub struct DiscordClient {
@@ -135,9 +189,33 @@ impl DiscordClient {
         ).unwrap()
         
     }
-    pub fn game(&mut self, stage: OptionalMeleeStage, character: OptionalMeleeCharacter, mode: String, timestamp: DiscordClientRequestTimestamp, opp_name: Option<String>) {
+    pub fn game(&mut self, request: DiscordClientRequest) {
+        let DiscordClientRequest { stage, character, mode, timestamp, opp_name, team_names, score, record, progress, matchup, stocks, party_size, win_loss, rules, partner, paused, paused_clock, .. } = request;
+        let state = opp_name.and_then(|n| Some(format!("Playing against {}", n)))
+            .or(team_names.and_then(|t| Some(t.to_string())))
+            .or(match (progress, record) {
+                (Some(p), Some(r)) => Some(format!("{} | {}: {}", p, if r.is_new { "New record" } else { "PB" }, r.record)),
+                (Some(p), None) => Some(p.to_string()),
+                (None, Some(r)) => Some(format!("{}: {}", if r.is_new { "New record" } else { "PB" }, r.record)),
+                (None, None) => None
+            })
+            .or(partner.and_then(|p| Some(format!("Training with {}", p))))
+            .or(matchup.and_then(|m| Some(m.to_string())))
+            .unwrap_or("In Game".into());
+        // Playing against X | 3 stocks (45%) vs 2 (120%)
+        let state = stocks.and_then(|s| Some(format!("{} | {}", state, s))).unwrap_or(state);
+        // Paused at 2:13 | Playing against X
+        let state = match (paused, paused_clock) {
+            (true, Some(clock)) => format!("Paused at {}:{:02} | {}", clock / 60, clock % 60, state),
+            (true, None) => format!("Paused | {}", state),
+            (false, _) => state
+        };
+        let mut activity = activity::Activity::new();
+        if let Some((players, max_players)) = party_size {
+            activity = activity.party(Party::new().size([players as i32, max_players as i32]));
+        }
         self.client.set_activity(
-            activity::Activity::new()
+            activity
                 .assets(
                     activity::Assets::new()
                         .large_image(stage.as_discord_resource().as_str())
@@ -149,8 +227,44 @@ impl DiscordClient {
                     if timestamp.mode == DiscordClientRequestTimestampMode::None { Timestamps::new() }
                     else if (timestamp.mode as u8) < (DiscordClientRequestTimestampMode::End as u8) { Timestamps::new().start(timestamp.timestamp) }
                     else { Timestamps::new().end(timestamp.timestamp) })
+                .details(
+                    // Ranked | 1 - 0, Game 2 | Session: 7W - 3L, or Vs. Mode | 4-stock, 8:00, items off
+                    [Some(mode), score.and_then(|s| Some(s.to_string())), rules.and_then(|r| Some(r.to_string())).filter(|r| !r.is_empty()), win_loss.and_then(|w| Some(format!("Session: {}", w)))]
+                        .into_iter().flatten().collect::<Vec<String>>().join(" | ").as_str())
+                .state(state.as_str())
+        ).unwrap()
+    }
+    pub fn stage_striking(&mut self, stage: OptionalMeleeStage, opp_name: Option<String>) {
+        // show the picked stage as soon as the strikes are done
+        let (large_image, large_text) = if stage.0.is_some() {
+            (stage.as_discord_resource(), format!("Picked {}", stage))
+        } else {
+            ("slippi".into(), "Stage Striking".into())
+        };
+        self.client.set_activity(
+            activity::Activity::new()
+                .assets(
+                    activity::Assets::new()
+                        .large_image(large_image.as_str())
+                        .large_text(large_text.as_str())
+                )
+                .timestamps(self.current_timestamp())
+                .details(SlippiMenuScene::Ranked.to_string().as_str())
+                .state(opp_name.and_then(|n| Some(format!("Stage striking vs {}", n))).unwrap_or("Stage striking".into()).as_str())
+        ).unwrap()
+    }
+    pub fn session(&mut self, mode: String, timestamp: DiscordClientRequestTimestamp, games_played: u32, win_loss: Option<WinLoss>) {
+        let games = format!("{} game{} played", games_played, if games_played == 1 { "" } else { "s" });
+        self.client.set_activity(
+            activity::Activity::new()
+                .assets(
+                    activity::Assets::new()
+                        .large_image("slippi")
+                        .large_text(mode.as_str())
+                )
+                .timestamps(Timestamps::new().start(timestamp.timestamp))
                 .details(mode.as_str())
-                .state(opp_name.and_then(|n| Some(format!("Playing against {}", n))).unwrap_or("In Game".into()).as_str())
+                .state(win_loss.and_then(|w| Some(format!("{} | {}", games, w))).unwrap_or(games).as_str())
         ).unwrap()
     }
     pub fn close(&mut self) {
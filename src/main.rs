@@ -1,11 +1,7 @@
-// TODO Sessions - each scene has a minor 0 which is the css. if you leave the major scene, the session ends, otherwise when not in-game we show when the session started
-// ^ option name "Show overall game session when not in-game" 
-// TODO HRC & BTT Records in discord
-// TODO Ranked match score, button "Viw opponent ranked profile", show details in stage striking already (in discord rich presence, signalize that you are in stage striking as well)
+// TODO Ranked: button "Viw opponent ranked profile"
 // TODO clean up melee.rs, move structs/enums away in coherent bundles
 
 // #![windows_subsystem = "windows"]
-#![feature(generic_const_exprs)]
 
 #[macro_use]
 extern crate serde_derive;
@@ -16,7 +12,7 @@ use tokio_util::sync::CancellationToken;
 use tokio::sync::mpsc;
 use util::sleep;
 
-use crate::tray::MeleeTrayEvent;
+use crate::{tray::MeleeTrayEvent, config::CONFIG};
 
 mod config;
 mod discord;
@@ -27,6 +23,17 @@ mod melee;
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(path) = arg_value(&args, "--dump") {
+        inspect_dump(&path);
+        return;
+    }
+    if let Some(path) = arg_value(&args, "--replay") {
+        replay_trace(&path);
+        return;
+    }
+    let record_path = arg_value(&args, "--record");
+
     let instance = SingleInstance::new("SLIPPI_DISCORD_RICH_PRESENCE_MTX").unwrap();
     assert!(instance.is_single());
     let (tx, mut rx) = mpsc::channel::<DiscordClientRequest>(32);
@@ -39,7 +46,14 @@ async fn main() {
             let discord_tx = tx.clone();
             let tray_tx = mtx.clone();
             let c_token = melee_cancel_token.clone();
+            let record_path = record_path.clone();
             let res = tokio::task::spawn_blocking(move || {
+                if let Some(path) = record_path {
+                    match melee::MeleeClient::recording(&path) {
+                        Ok(mut client) => return client.run(c_token, discord_tx, tray_tx),
+                        Err(err) => println!("[ERROR] Couldn't open trace file {}: {}", path, err)
+                    }
+                }
                 let mut client = melee::MeleeClient::new();
                 client.run(c_token, discord_tx, tray_tx);
             }).await;
@@ -70,7 +84,9 @@ async fn main() {
                 println!("{:?}", msg);
                 match msg.req_type {
                     DiscordClientRequestType::Queue => discord_client.queue(msg.scene, msg.character).await,
-                    DiscordClientRequestType::Game => discord_client.game(msg.stage, msg.character, msg.mode, msg.timestamp, msg.opp_name),
+                    DiscordClientRequestType::Game => discord_client.game(msg),
+                    DiscordClientRequestType::StageStriking => discord_client.stage_striking(msg.stage, msg.opp_name),
+                    DiscordClientRequestType::Session => discord_client.session(msg.mode, msg.timestamp, msg.games_played, msg.win_loss),
                     DiscordClientRequestType::Clear => discord_client.clear()
                 }
             }
@@ -83,4 +99,40 @@ async fn main() {
 
     // cleanup
     cancel_token.cancel();
-}
\ No newline at end of file
+}
+
+fn arg_value(args: &[String], name: &str) -> Option<String> {
+    args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).cloned()
+}
+
+// prints what we detect from a raw MEM1 dump, useful to reproduce bug reports without a running dolphin
+fn inspect_dump(path: &str) {
+    let dump = match melee::MemoryDump::open(path) {
+        Ok(dump) => dump,
+        Err(err) => {
+            println!("[ERROR] Couldn't open memory dump {}: {}", path, err);
+            return;
+        }
+    };
+    let mut client = melee::MeleeClient::with_source(dump);
+    println!("Game: {:?}", client.detect_game());
+    println!("Scene: {:?}", client.get_melee_scene());
+    println!("Stage: {:?}", client.get_stage());
+    println!("Character: {:?}", client.get_character(0));
+    println!("Request: {:?}", CONFIG.with_ref(|c| client.presence_request(c)));
+}
+
+// runs the melee client against a recorded trace and prints every payload it would have sent to discord and every event, in order
+fn replay_trace(path: &str) {
+    let replay = match melee::TraceReplay::open(path) {
+        Ok(replay) => replay,
+        Err(err) => {
+            println!("[ERROR] Couldn't open trace file {}: {}", path, err);
+            return;
+        }
+    };
+    let mut client = melee::MeleeClient::with_source(replay);
+    for output in client.replay() {
+        println!("{:?}", output);
+    }
+}
@@ -1,33 +1,58 @@
-use std::{fmt::Display};
+use std::{fmt::Display, io, path::Path};
 
 use num_enum::TryFromPrimitive;
-use strum::{IntoEnumIterator};
-use strum_macros::{Display, EnumIter};
+use strum_macros::Display;
 use tokio_util::sync::CancellationToken;
 
-use crate::{discord::{DiscordClientRequest, DiscordClientRequestType, DiscordClientRequestTimestamp, DiscordClientRequestTimestampMode}, util::{current_unix_time, sleep}, melee::{stage::MeleeStage, character::MeleeCharacter}, config::{CONFIG}, tray::MeleeTrayEvent};
+use crate::{discord::{DiscordClientRequest, DiscordClientRequestTimestamp, DiscordClientRequestTimestampMode}, melee::{stage::MeleeStage, character::MeleeCharacter}, config::{CONFIG, AppConfig, DolphinSelection}, tray::MeleeTrayEvent};
 
-use self::{dolphin_mem::{DolphinMemory, util::R13}, msrb::MSRBOffset, multiman::MultiManVariant};
+use self::{dolphin_mem::{DolphinMemory, MemorySource, SnapshotMemory, TraceRecorder}, events::{MeleeObservation, SceneStateMachine}, session::SessionTracker, special::SpecialMeleeVariant, game::{AddressProfile, MeleeGame, SceneKind, GAME_ID_ADDR, GAME_ID_LEN, GAME_REVISION_ADDR}, msrb::{MSRBOffset, MSRBMemory}, multiman::MultiManVariant, ranked::RankedSet, rules::{TimerMode, MATCH_INIT_LEN}, records::{PersonalRecord, CHARACTER_COUNT, CHARACTER_RECORDS_LEN}, player::{PlayerState, PLAYER_BLOCK_LEN, StaticPlayerBlock}};
+
+pub use self::dolphin_mem::{DolphinInstance, MemoryDump, TraceReplay};
+pub use self::events::MeleeEvent;
+pub use self::ranked::RankedSetScore;
+pub use self::records::RecordStatus;
+pub use self::rules::MatchRules;
+pub use self::results::WinLoss;
+pub use self::stadium::StadiumProgress;
+pub use self::player::{Matchup, PlayerCharacter, StockStatus};
+pub use self::teams::TeamNames;
 
 mod dolphin_mem;
+mod events;
+mod game;
+mod instance;
 mod msrb;
 mod multiman;
+mod player;
+mod ranked;
+mod records;
+mod results;
+mod rules;
+mod session;
+mod special;
+mod stadium;
+mod teams;
+#[cfg(test)]
+mod fixtures;
 pub mod stage;
 pub mod character;
 pub mod dolphin_user;
 
-// reference: https://github.com/akaneia/m-ex/blob/master/MexTK/include/match.h#L11-L14
-#[derive(PartialEq, EnumIter, Clone, Copy)]
-enum TimerMode {
-    Countup = 3,
-    Countdown = 2,
-    Hidden = 1,
-    Frozen = 0,
-}
+const RUN_INTERVAL: u64 = 1000;
+const INSTANCE_SCAN_INTERVAL: u32 = 5;
+// Discord allows 5 activity updates per 20 seconds, so changes that only concern the live state of a game wait a bit
+const LIVE_UPDATE_INTERVAL: i64 = 5;
+const PLAYER_SELECTION_BLOCK_LEN: usize = 0x08;
+const MATCH_INFO_LRAS_INITIATOR: u32 = 0x01; // port of whoever quit out
+const MATCH_INFO_END_METHOD: u32 = 0x08;
+// offsets from r13
+const R13_PLAYER_PORT: u32 = 0x5108;
+const R13_SLIPPI_ONLINE_SCENE: u32 = 0x5060;
 
 #[derive(TryFromPrimitive, Display, Debug)]
 #[repr(u8)]
-enum MatchmakingMode {
+pub enum MatchmakingMode {
     Idle = 0,
     Initializing = 1,
     Matchmaking = 2,
@@ -45,22 +70,48 @@ pub enum SlippiMenuScene {
     Teams = 3
 }
 
-pub struct MeleeClient {
-    mem: DolphinMemory,
+pub struct MeleeClient<M: MemorySource = DolphinMemory> {
+    mem: SnapshotMemory<M>,
+    game: Option<MeleeGame>,
+    profile: &'static AddressProfile,
+    scene_state: SceneStateMachine,
+    session: SessionTracker,
+    ranked_set: Option<RankedSet>,
+    striking_baseline: Option<u32>, // rng offset of the last ranked game
+    record_baseline: Option<Option<PersonalRecord>>, // the record at the start of the current attempt
+    attempt_frames: (Option<u32>, Option<u32>), // frame count at the start and end of the current Stadium attempt
     last_payload: DiscordClientRequest,
-    last_tray_event: MeleeTrayEvent
+    last_payload_time: i64,
+    last_tray_event: MeleeTrayEvent,
+    last_instances: (Vec<DolphinInstance>, Option<u32>),
+    last_selection: Option<(DolphinSelection, Option<String>)>,
+    instance_scan: u32
+}
+
+// what the client produced during a tick, in the order it happened
+#[derive(Debug, PartialEq, Clone)]
+pub enum MeleeClientOutput {
+    Event(MeleeEvent),
+    Presence(Box<DiscordClientRequest>)
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum MeleeScene {
     VsMode,
     UnclePunch,
     TrainingMode,
     SlippiOnline(Option<SlippiMenuScene>),
     SlippiCss(Option<SlippiMenuScene>),
+    StageStriking, // ranked only, between locking in characters and the game loading
     HomeRunContest,
     TargetTest(Option<MeleeStage>),
-    MultiManMelee(MultiManVariant)
+    MultiManMelee(MultiManVariant),
+    Classic,
+    Adventure,
+    AllStar,
+    EventMatch,
+    SpecialMelee(SpecialMeleeVariant),
+    TournamentMelee
 }
 
 impl Display for MeleeScene {
@@ -71,6 +122,7 @@ impl Display for MeleeScene {
             Self::TrainingMode => write!(f, "Training Mode"),
             Self::SlippiOnline(Some(scene)) => write!(f, "{}", scene),
             Self::SlippiOnline(None) => write!(f, "Slippi Online"),
+            Self::StageStriking => write!(f, "Stage Striking"),
             Self::HomeRunContest => write!(f, "Home-Run Contest"),
             Self::TargetTest(stage_opt) => {
                 if stage_opt.is_some() && CONFIG.with_ref(|c| c.stadium.btt.show_stage_name) {
@@ -87,202 +139,426 @@ impl Display for MeleeScene {
                 MultiManVariant::Endless => "Endless",
                 MultiManVariant::Cruel => "Cruel",
             }),
+            Self::Classic => write!(f, "Classic Mode"),
+            Self::Adventure => write!(f, "Adventure Mode"),
+            Self::AllStar => write!(f, "All-Star Mode"),
+            Self::EventMatch => write!(f, "Event Match"),
+            Self::SpecialMelee(variant) => write!(f, "Special Melee ({})", variant),
+            Self::TournamentMelee => write!(f, "Tournament Melee"),
             Self::SlippiCss(_) => unimplemented!(),
         }
     }
 }
 
+impl MeleeScene {
+    // anything but the menus around a game
+    pub fn is_in_game(&self) -> bool {
+        !matches!(self, Self::SlippiCss(_) | Self::StageStriking)
+    }
+    // local multiplayer matches, with all the rules of Vs. Mode
+    pub fn is_vs(&self) -> bool {
+        matches!(self, Self::VsMode | Self::SpecialMelee(_) | Self::TournamentMelee)
+    }
+}
+
 impl MeleeClient {
     pub fn new() -> Self {
-        MeleeClient { mem: DolphinMemory::new(), last_payload: DiscordClientRequest::clear(), last_tray_event: MeleeTrayEvent::Disconnected }
+        Self::with_source(DolphinMemory::new())
+    }
+}
+
+impl MeleeClient<TraceRecorder<DolphinMemory>> {
+    // like new, but every memory read is appended to a trace file which can be replayed with TraceReplay
+    pub fn recording<P: AsRef<Path>>(trace_path: P) -> io::Result<Self> {
+        Ok(Self::with_source(TraceRecorder::create(trace_path, DolphinMemory::new())?))
     }
+}
 
-    fn get_player_port(&mut self) -> Option<u8> { self.mem.read::<u8>(R13!(0x5108)) }
+impl<M: MemorySource> MeleeClient<M> {
+    pub fn with_source(mem: M) -> Self {
+        MeleeClient { mem: SnapshotMemory::new(mem), game: None, profile: AddressProfile::fallback(), scene_state: SceneStateMachine::new(), session: SessionTracker::new(), ranked_set: None, striking_baseline: None, record_baseline: None, attempt_frames: (None, None), last_payload: DiscordClientRequest::clear(), last_payload_time: 0, last_tray_event: MeleeTrayEvent::Disconnected, last_instances: (Vec::new(), None), last_selection: None, instance_scan: 0 }
+    }
+
+    // fetches everything a presence update needs with one read per region
+    fn take_snapshot(&mut self) {
+        self.mem.clear();
+        self.mem.capture(GAME_ID_ADDR, 0x08);
+        if self.detect_game().and_then(|g| g.address_profile()).is_none() {
+            return;
+        }
+
+        let p = self.profile;
+        self.mem.capture(p.major_scene, 0x04);
+        self.mem.capture(p.r13(R13_PLAYER_PORT), (R13_PLAYER_PORT - R13_SLIPPI_ONLINE_SCENE) as usize + 0x01);
+        self.mem.capture(p.stage_info + 0x88, 0x04);
+        self.mem.capture(p.game_time, 0x04);
+        self.mem.capture(p.match_info + MATCH_INFO_LRAS_INITIATOR, (MATCH_INFO_END_METHOD - MATCH_INFO_LRAS_INITIATOR) as usize + 0x01);
+        self.mem.capture(p.match_init, MATCH_INIT_LEN);
+        self.mem.capture(p.player_selection_blocks[0], (p.player_selection_blocks[3] - p.player_selection_blocks[0]) as usize + PLAYER_SELECTION_BLOCK_LEN);
+        self.mem.capture(p.player_blocks[0], (p.player_blocks[3] - p.player_blocks[0]) as usize + PLAYER_BLOCK_LEN);
+        if let Some(records) = p.character_records {
+            self.mem.capture(records, CHARACTER_COUNT * CHARACTER_RECORDS_LEN);
+        }
+        if let Some(frame_count) = p.frame_count {
+            self.mem.capture(frame_count, 0x04);
+        }
+        self.mem.capture_msrb();
+    }
+
+    // None if no game is running (yet)
+    pub fn detect_game(&mut self) -> Option<MeleeGame> {
+        let game = self.read_game();
+        if game != self.game {
+            match &game {
+                Some(game) if game.address_profile().is_none() => println!("Unsupported game: {} (no address profile for it, see profiles.json)", game),
+                Some(game) => println!("Detected game: {}", game),
+                None => {}
+            }
+            self.profile = game.as_ref().and_then(|g| g.address_profile()).unwrap_or(AddressProfile::fallback());
+            self.game = game;
+        }
+        self.game.clone()
+    }
+    fn read_game(&mut self) -> Option<MeleeGame> {
+        self.mem.read_string::<GAME_ID_LEN>(GAME_ID_ADDR)
+            .filter(|game_id| !game_id.is_empty())
+            .map(|id| MeleeGame { id, revision: self.mem.read::<u8>(GAME_REVISION_ADDR).unwrap_or(0) })
+    }
+    fn is_unsupported_game(&self) -> bool {
+        self.game.as_ref().map(|g| g.address_profile().is_none()).unwrap_or(false)
+    }
+
+    fn get_player_port(&mut self) -> Option<u8> { self.mem.read::<u8>(self.profile.r13(R13_PLAYER_PORT)) }
     fn get_slippi_player_port(&mut self) -> Option<u8> { self.mem.read_msrb(MSRBOffset::MsrbLocalPlayerIndex) }
+    fn get_local_port(&mut self, scene: MeleeScene) -> Option<u8> {
+        match scene {
+            scene if scene.is_vs() => self.get_player_port(),
+            // whoever picked the character, like on any other css
+            MeleeScene::HomeRunContest | MeleeScene::TargetTest(_) | MeleeScene::MultiManMelee(_) => self.get_player_port(),
+            MeleeScene::SlippiOnline(_) => self.get_slippi_player_port(),
+            _ => Some(0u8) // default to port 1, mostly the case in single player modes like training mode/unclepunch
+        }
+    }
+    fn lras_initiator(&mut self) -> Option<u8> { self.mem.read::<u8>(self.profile.match_info + MATCH_INFO_LRAS_INITIATOR).filter(|port| *port < 4) }
     fn get_opp_name(&mut self) -> Option<String> { self.mem.read_msrb_string::<31>(MSRBOffset::MsrbOppName) }
     fn get_player_connect_code(&mut self, port: u8) -> Option<String> {
         const PLAYER_CONNECTCODE_OFFSETS: [MSRBOffset; 4] = [MSRBOffset::MsrbP1ConnectCode, MSRBOffset::MsrbP2ConnectCode, MSRBOffset::MsrbP3ConnectCode, MSRBOffset::MsrbP4ConnectCode];
         self.mem.read_msrb_string_shift_jis::<10>(PLAYER_CONNECTCODE_OFFSETS[port as usize])
     }
     fn get_character_selection(&mut self, port: u8) -> Option<MeleeCharacter> {
-        // 0x04 = character, 0x05 = skin (reference: https://github.com/bkacjios/m-overlay/blob/master/source/modules/games/GALE01-2.lua#L199-L202)
-        const PLAYER_SELECTION_BLOCKS: [u32; 4] = [0x8043208B, 0x80432093, 0x8043209B, 0x804320A3];
-        self.mem.read::<u8>(PLAYER_SELECTION_BLOCKS[port as usize] + 0x04).and_then(|v| MeleeCharacter::try_from(v).ok())
+        self.mem.read::<u8>(self.profile.player_selection_blocks[port as usize] + 0x04).and_then(|v| MeleeCharacter::try_from(v).ok())
     }
-    fn timer_mode(&mut self) -> TimerMode {
-        const MATCH_INIT: u32 = 0x8046DB68; // first byte, reference: https://github.com/akaneia/m-ex/blob/master/MexTK/include/match.h#L136
-        self.mem.read::<u8>(MATCH_INIT).and_then(|v| {
-            for timer_mode in TimerMode::iter() {
-                let val = timer_mode as u8;
-                if v & val == val {
-                    return Some(timer_mode);
-                }
-            }
-            None
-        }).unwrap_or(TimerMode::Countup)
+    fn get_costume_selection(&mut self, port: u8) -> Option<u8> {
+        self.mem.read::<u8>(self.profile.player_selection_blocks[port as usize] + 0x05)
     }
-    fn game_time(&mut self) -> i64 { self.mem.read::<u32>(0x8046B6C8).and_then(|v| Some(v)).unwrap_or(0) as i64 }
+    fn timer_mode(&mut self) -> TimerMode { self.match_rules().and_then(|r| r.timer).unwrap_or(TimerMode::Countup) }
+    fn game_time(&mut self) -> i64 { self.mem.read::<u32>(self.profile.game_time).and_then(|v| Some(v)).unwrap_or(0) as i64 }
     fn matchmaking_type(&mut self) -> Option<MatchmakingMode> {
         self.mem.read_msrb::<u8>(MSRBOffset::MsrbConnectionState).and_then(|v| MatchmakingMode::try_from(v).ok())
     }
-    fn slippi_online_scene(&mut self) -> Option<SlippiMenuScene> { self.mem.read::<u8>(R13!(0x5060)).and_then(|v| SlippiMenuScene::try_from(v).ok()) }
-    /*fn game_variant(&mut self) -> Option<MeleeGameVariant> {
-        const GAME_ID_ADDR: u32 = 0x80000000;
-        const GAME_ID_LEN: usize = 0x06;
-
-        let game_id = self.mem.read_string::<GAME_ID_LEN>(GAME_ID_ADDR);
-        if game_id.is_none() {
-            return None;
+    fn slippi_online_scene(&mut self) -> Option<SlippiMenuScene> { self.mem.read::<u8>(self.profile.r13(R13_SLIPPI_ONLINE_SCENE)).and_then(|v| SlippiMenuScene::try_from(v).ok()) }
+    fn scene_tuple(&mut self) -> (u8, u8) {
+        (self.mem.read::<u8>(self.profile.major_scene).unwrap_or(0), self.mem.read::<u8>(self.profile.minor_scene()).unwrap_or(0))
+    }
+    fn scene_from_kind(&mut self, kind: SceneKind) -> MeleeScene {
+        match kind {
+            SceneKind::VsMode => MeleeScene::VsMode,
+            SceneKind::UnclePunch => MeleeScene::UnclePunch,
+            SceneKind::TrainingMode => MeleeScene::TrainingMode,
+            SceneKind::SlippiOnline => MeleeScene::SlippiOnline(self.slippi_online_scene()),
+            SceneKind::SlippiCss => MeleeScene::SlippiCss(self.slippi_online_scene()),
+            SceneKind::HomeRunContest => MeleeScene::HomeRunContest,
+            SceneKind::TargetTest => MeleeScene::TargetTest(self.get_stage()),
+            SceneKind::MultiManMelee(variant) => MeleeScene::MultiManMelee(variant),
+            SceneKind::Classic => MeleeScene::Classic,
+            SceneKind::Adventure => MeleeScene::Adventure,
+            SceneKind::AllStar => MeleeScene::AllStar,
+            SceneKind::EventMatch => MeleeScene::EventMatch,
+            SceneKind::SpecialMelee(variant) => MeleeScene::SpecialMelee(variant),
+            SceneKind::TournamentMelee => MeleeScene::TournamentMelee
         }
-        return match game_id.unwrap().as_str() {
-            "GALE01" => Some(MeleeGameVariant::Vanilla),
-            "GTME01" => Some(MeleeGameVariant::UnclePunch),
-            _ => None
+    }
+    pub fn get_melee_scene(&mut self) -> Option<MeleeScene> {
+        let game = self.game.clone()?;
+        self.scene_of(&game)
+    }
+    // the scene as the given game maps it, with the current profile
+    fn scene_of(&mut self, game: &MeleeGame) -> Option<MeleeScene> {
+        let (major, minor) = self.scene_tuple();
+        match self.profile.scene(game, major, minor).map(|kind| self.scene_from_kind(kind)) {
+            Some(MeleeScene::SlippiCss(Some(SlippiMenuScene::Ranked))) if self.is_stage_striking() => Some(MeleeScene::StageStriking),
+            scene => scene
         }
-    }*/
-    fn get_melee_scene(&mut self) -> Option<MeleeScene> {
-        const MAJOR_SCENE: u32 = 0x80479D30;
-        const MINOR_SCENE: u32 = MAJOR_SCENE + 0x03;
-        let scene_tuple = (self.mem.read::<u8>(MAJOR_SCENE).unwrap_or(0), self.mem.read::<u8>(MINOR_SCENE).unwrap_or(0));
-
-        match scene_tuple {
-            (2, 2) => Some(MeleeScene::VsMode),
-            (43, 1) => Some(MeleeScene::UnclePunch),
-            (28, 2) => Some(MeleeScene::TrainingMode),
-            (8, 2) => Some(MeleeScene::SlippiOnline(self.slippi_online_scene())),
-            (8, 0) => Some(MeleeScene::SlippiCss(self.slippi_online_scene())),
-            (32, 1) => Some(MeleeScene::HomeRunContest),
-            (15, 1) => Some(MeleeScene::TargetTest(self.get_stage())),
-            (33, 1) => Some(MeleeScene::MultiManMelee(MultiManVariant::TenMan)),
-            (34, 1) => Some(MeleeScene::MultiManMelee(MultiManVariant::HundredMan)),
-            (35, 1) => Some(MeleeScene::MultiManMelee(MultiManVariant::ThreeMinute)),
-            (36, 1) => Some(MeleeScene::MultiManMelee(MultiManVariant::FifteenMinute)),
-            (37, 1) => Some(MeleeScene::MultiManMelee(MultiManVariant::Endless)),
-            (38, 1) => Some(MeleeScene::MultiManMelee(MultiManVariant::Cruel)),
-            _ => None
+    }
+    fn observe(&mut self) -> Option<MeleeObservation> {
+        if !self.mem.has_process() || self.detect_game().and_then(|g| g.address_profile()).is_none() {
+            return None;
         }
+        let (major, minor) = self.scene_tuple();
+        Some(MeleeObservation {
+            major,
+            minor,
+            scene: self.get_melee_scene(),
+            mode: self.game.clone().and_then(|game| self.profile.mode(&game, major)).map(|kind| self.scene_from_kind(kind)),
+            matchmaking: self.matchmaking_type(),
+            game_time: self.game_time() as u32,
+            timer_running: matches!(self.timer_mode(), TimerMode::Countup | TimerMode::Countdown),
+            end_method: self.mem.read::<u8>(self.profile.match_info + MATCH_INFO_END_METHOD).unwrap_or(0)
+        })
+    }
+    pub fn get_stage(&mut self) -> Option<MeleeStage> {
+        self.mem.read::<u8>(self.profile.stage_info + 0x88 + 0x03).and_then(|v| MeleeStage::try_from(v).ok())
+    }
+    pub fn get_character(&mut self, player_id: u8) -> Option<MeleeCharacter> {
+        self.mem.read::<StaticPlayerBlock>(self.profile.player_blocks[player_id as usize]).and_then(|b| MeleeCharacter::try_from(b.character as u8).ok())
+    }
+    pub fn get_costume(&mut self, player_id: u8) -> Option<u8> {
+        self.mem.read::<StaticPlayerBlock>(self.profile.player_blocks[player_id as usize]).map(|b| b.costume)
     }
-    fn get_stage(&mut self) -> Option<MeleeStage> {
-        self.mem.read::<u8>(0x8049E6C8 + 0x88 + 0x03).and_then(|v| MeleeStage::try_from(v).ok())
+    // everyone who takes part in the current game
+    pub fn get_players(&mut self) -> Vec<PlayerState> {
+        let blocks = self.profile.player_blocks;
+        (0..4u8).filter_map(|port| self.mem.read::<StaticPlayerBlock>(blocks[port as usize]).and_then(|b| PlayerState::from_block(port, &b))).collect()
     }
-    fn get_character(&mut self, player_id: u8) -> Option<MeleeCharacter> {
-        const PLAYER_BLOCKS: [u32; 4] = [0x80453080, 0x80453F10, 0x80454DA0, 0x80455C30];
-        self.mem.read::<u8>(PLAYER_BLOCKS[player_id as usize] + 0x07).and_then(|v| MeleeCharacter::try_from(v).ok())
+    // whoever isn't us, usually a CPU. What the CPU is set to do (stand, walk, jump...) lives in the training menu's data,
+    // which we don't know the layout of, so only the character and level are shown
+    fn training_partner(&mut self, local_port: u8) -> Option<PlayerCharacter> {
+        self.get_players().iter().find(|p| p.port != local_port).map(PlayerCharacter::of)
     }
+    fn is_teams(&mut self) -> bool { self.match_rules().map(|r| r.teams).unwrap_or(false) }
 
-    pub fn run(&mut self, stop_signal: CancellationToken, discord_send: tokio::sync::mpsc::Sender<DiscordClientRequest>, tray_send: std::sync::mpsc::Sender<MeleeTrayEvent>) {
-        const RUN_INTERVAL: u64 = 1000;
-        macro_rules! send_discord_msg {
-            ($req:expr) => {
-                if self.last_payload != $req {
-                    let _ = discord_send.blocking_send($req);
-                    self.last_payload = $req;
+    // the presence for the current state of the game, None if the last one should be kept
+    pub fn presence_request(&mut self, c: &AppConfig) -> Option<DiscordClientRequest> {
+        // don't guess scenes from addresses that mean something else in this game
+        if self.detect_game().and_then(|g| g.address_profile()).is_none() {
+            return Some(DiscordClientRequest::clear());
+        }
+        let gamemode_opt = self.get_melee_scene();
+        if gamemode_opt.is_some() {
+            let gamemode = gamemode_opt.unwrap();
+
+            if gamemode == MeleeScene::StageStriking {
+                return Some(if c.slippi.ranked.show_stage_striking && gamemode.is_enabled(c) { self.stage_striking_request(c) } else { self.session_request(c) });
+            }
+
+            // Check if we are queueing a game
+            if c.slippi.enabled && c.slippi.show_queueing && match gamemode {
+                MeleeScene::SlippiCss(scene) =>
+                    scene.and_then(|s| Some(s.is_enabled(c))).unwrap_or(true),
+                _ => false
+            } {
+                match self.matchmaking_type() {
+                    Some(MatchmakingMode::Initializing) | Some(MatchmakingMode::Matchmaking) => {
+                        let port_op = self.get_player_port();
+                        if !port_op.is_none() {
+                            let port = port_op.unwrap();
+                            let character = if c.global.show_in_game_character { self.get_character_selection(port) } else { Some(MeleeCharacter::Hidden) };
+                            let costume = if c.global.show_in_game_character { self.get_costume_selection(port) } else { None };
+                            match gamemode {
+                                MeleeScene::SlippiCss(scene) => {
+                                    let request = DiscordClientRequest::queue(
+                                        scene,
+                                        character,
+                                        costume
+                                    );
+                                    return Some(request);
+                                },
+                                _ => {/* shouldn't happen */}
+                            }
+                        }
+                    }
+                    Some(_) => {
+                        return Some(self.session_request(c));
+                    }, // sometimes it's none, probably because the pointer indirection changes during the asynchronous memory requests
+                    _ => {}
                 }
-            };
+            // Else, we want to see if the current game mode is enabled in the config (we're in-game)
+            } else if gamemode.is_in_game() && gamemode.is_enabled(c) { // if we are in css, ignore
+                let game_time = self.game_time();
+                let paused = self.scene_state.is_paused();
+                // discord can't stop a running timestamp, so we hide it until the game goes on and then anchor it to the game time again
+                let timestamp = if c.global.show_in_game_time && !paused {
+                    DiscordClientRequestTimestamp {
+                        mode: match self.timer_mode() {
+                            TimerMode::Countdown => DiscordClientRequestTimestampMode::End,
+                            TimerMode::Frozen => DiscordClientRequestTimestampMode::Static,
+                            _ => DiscordClientRequestTimestampMode::Start
+                        },
+                        timestamp: if self.timer_mode() == TimerMode::Countdown { self.mem.current_time() + game_time } else { self.mem.current_time() - game_time }
+                    }
+                } else {
+                    DiscordClientRequestTimestamp::none()
+                };
+                let player_index = self.get_local_port(gamemode).unwrap_or(0u8);
+                let players = match gamemode {
+                    MeleeScene::SlippiOnline(_) => self.get_players(),
+                    scene if scene.is_vs() => self.get_players(),
+                    _ => Vec::new()
+                };
+                let teams = self.is_teams();
+                // singles on Slippi already show who we're playing against
+                let show_party = gamemode.is_vs() || gamemode == MeleeScene::SlippiOnline(Some(SlippiMenuScene::Teams));
+                let matchup = if show_party && c.global.show_in_game_character { Matchup::new(&players, Some(player_index), teams) } else { None };
+                let stocks = if c.global.show_stocks { StockStatus::new(&players, Some(player_index), teams) } else { None };
+                let request = DiscordClientRequest {
+                    // there's no single opponent in teams
+                    opp_name: if match gamemode { MeleeScene::SlippiOnline(Some(SlippiMenuScene::Teams)) => false, MeleeScene::SlippiOnline(_) => true, _ => false } && c.slippi.show_opponent_name { self.get_opp_name() } else { None },
+                    team_names: if gamemode == MeleeScene::SlippiOnline(Some(SlippiMenuScene::Teams)) && c.slippi.show_opponent_name { self.get_team_names() } else { None },
+                    score: if gamemode == MeleeScene::SlippiOnline(Some(SlippiMenuScene::Ranked)) && c.slippi.ranked.show_score { self.ranked_score() } else { None },
+                    record: match gamemode {
+                        MeleeScene::HomeRunContest if c.stadium.hrc.show_record => self.record_status(gamemode),
+                        MeleeScene::TargetTest(_) if c.stadium.btt.show_record => self.record_status(gamemode),
+                        MeleeScene::MultiManMelee(_) if c.stadium.mmm.show_record => self.record_status(gamemode),
+                        _ => None
+                    },
+                    progress: match gamemode {
+                        MeleeScene::HomeRunContest if c.stadium.hrc.show_progress => self.stadium_progress(gamemode),
+                        MeleeScene::TargetTest(_) if c.stadium.btt.show_progress => self.stadium_progress(gamemode),
+                        MeleeScene::MultiManMelee(_) if c.stadium.mmm.show_progress => self.stadium_progress(gamemode),
+                        _ => None
+                    },
+                    matchup,
+                    stocks,
+                    party_size: if !show_party || players.is_empty() { None } else { Some((players.len() as u8, 4)) },
+                    win_loss: if c.global.show_win_loss { self.session_win_loss() } else { None },
+                    rules: if gamemode.is_vs() && c.vs_mode.show_rules { self.match_rules() } else { None },
+                    partner: if gamemode == MeleeScene::TrainingMode && c.training_mode.show_partner && c.global.show_in_game_character { self.training_partner(player_index) } else { None },
+                    paused,
+                    paused_clock: if paused && c.global.show_in_game_time { Some(game_time as u32) } else { None },
+                    ..DiscordClientRequest::game(
+                        match gamemode { MeleeScene::TargetTest(scene) => scene, _ => self.get_stage() },
+                        if c.global.show_in_game_character { self.get_character(player_index) } else { Some(MeleeCharacter::Hidden) },
+                        if c.global.show_in_game_character { self.get_costume(player_index) } else { None },
+                        gamemode,
+                        timestamp
+                    )
+                };
+                
+                return Some(request);
+            } else {
+                return Some(self.session_request(c));
+            }
+        } else {
+            return Some(self.session_request(c));
         }
+        None
+    }
 
+    // shown instead of clearing the presence while we're in a mode but not in-game
+    fn session_request(&self, c: &AppConfig) -> DiscordClientRequest {
+        match self.session.session() {
+            Some(session) if c.global.show_session && session.mode.is_enabled(c) =>
+                DiscordClientRequest::session(session.mode, session.start, session.games, if c.global.show_win_loss { self.session_win_loss() } else { None }),
+            _ => DiscordClientRequest::clear()
+        }
+    }
+
+    // nothing to show until a game of the session had a winner
+    fn session_win_loss(&self) -> Option<WinLoss> {
+        self.session.session().map(|s| s.record).filter(|r| r.wins + r.losses > 0)
+    }
+
+    pub fn run(&mut self, stop_signal: CancellationToken, discord_send: tokio::sync::mpsc::Sender<DiscordClientRequest>, tray_send: std::sync::mpsc::Sender<MeleeTrayEvent>) {
         loop {
-            if stop_signal.is_cancelled() {
+            if stop_signal.is_cancelled() || self.mem.is_exhausted() {
                 return;
             }
-            if !self.mem.has_process() {
-                println!("{}", if self.mem.find_process() { "Found" } else { "Searching process..." });
+            // the events have already been handled by the client itself, only the payloads go out
+            for output in self.tick(&tray_send) {
+                if let MeleeClientOutput::Presence(request) = output {
+                    let _ = discord_send.blocking_send(*request);
+                }
+            }
+            self.mem.next_tick(RUN_INTERVAL);
+        }
+    }
+
+    // runs until the memory source runs out of data (e.g. a trace replay) and returns everything the client produced, in order
+    pub fn replay(&mut self) -> Vec<MeleeClientOutput> {
+        let (tray_send, _tray_recv) = std::sync::mpsc::channel::<MeleeTrayEvent>();
+        let mut outputs = Vec::new();
+        while !self.mem.is_exhausted() {
+            outputs.extend(self.tick(&tray_send));
+            self.mem.next_tick(RUN_INTERVAL);
+        }
+        outputs
+    }
+
+    // one iteration of the client, returns the events and the presence update (if any) of this tick
+    fn tick(&mut self, tray_send: &std::sync::mpsc::Sender<MeleeTrayEvent>) -> Vec<MeleeClientOutput> {
+        let mut outputs = Vec::new();
+        if !self.mem.has_process() {
+            let found = self.mem.find_process();
+            println!("{}", if found { "Found" } else { "Searching process..." });
+            if found {
+                self.instance_scan = 0;
+            }
+        } else {
+            self.mem.check_process_running();
+        }
+
+        // look for other dolphin instances every few ticks, or right away if the selection was changed in the tray
+        {
+            let selection = CONFIG.with_ref(|c| (c.dolphin.selection, c.dolphin.pinned_path.clone()));
+            let instances = if !self.mem.has_process() {
+                (Vec::new(), None)
+            } else if self.instance_scan == 0 || self.last_selection.as_ref() != Some(&selection) {
+                self.instance_scan = INSTANCE_SCAN_INTERVAL;
+                self.last_selection = Some(selection);
+                (CONFIG.with_ref(|c| self.select_instance(c)), self.mem.attached_pid())
             } else {
-                self.mem.check_process_running();
+                self.last_instances.clone()
+            };
+            self.instance_scan = self.instance_scan.saturating_sub(1);
+            if instances != self.last_instances {
+                self.last_instances = instances.clone();
+                let _ = tray_send.send(MeleeTrayEvent::Instances(instances.0, instances.1));
             }
+        }
 
-            {
-                let has_process = self.mem.has_process();
-                if has_process == (self.last_tray_event == MeleeTrayEvent::Disconnected) {
-                    let tray_ev = if has_process { MeleeTrayEvent::Connected } else { MeleeTrayEvent::Disconnected };
-                    self.last_tray_event = tray_ev;
-                    let _ = tray_send.send(tray_ev);
-                }
+        if self.mem.has_process() {
+            self.take_snapshot();
+        }
+
+        {
+            let tray_ev = if !self.mem.has_process() {
+                MeleeTrayEvent::Disconnected
+            } else if self.is_unsupported_game() {
+                MeleeTrayEvent::UnsupportedGame
+            } else {
+                MeleeTrayEvent::Connected
+            };
+            if tray_ev != self.last_tray_event {
+                self.last_tray_event = tray_ev.clone();
+                let _ = tray_send.send(tray_ev);
             }
+        }
 
-            CONFIG.with_ref(|c| {
-                // self.get_game_variant();
-                let gamemode_opt = self.get_melee_scene();
-                if gamemode_opt.is_some() {
-                    let gamemode = gamemode_opt.unwrap();
-
-                    // Check if we are queueing a game
-                    if c.slippi.enabled && c.slippi.show_queueing && match gamemode {
-                        MeleeScene::SlippiCss(scene) =>
-                            scene.and_then(|s| Some(s.is_enabled(c))).unwrap_or(true),
-                        _ => false
-                    } {
-                        match self.matchmaking_type() {
-                            Some(MatchmakingMode::Initializing) | Some(MatchmakingMode::Matchmaking) => {
-                                let port_op = self.get_player_port();
-                                if !port_op.is_none() {
-                                    let port = port_op.unwrap();
-                                    let character = if c.global.show_in_game_character { self.get_character_selection(port) } else { Some(MeleeCharacter::Hidden) };
-                                    match gamemode {
-                                        MeleeScene::SlippiCss(scene) => {
-                                            let request = DiscordClientRequest::queue(
-                                                scene,
-                                                character
-                                            );
-                                            send_discord_msg!(request.clone());
-                                        },
-                                        _ => {/* shouldn't happen */}
-                                    }
-                                }
-                            }
-                            Some(_) => {
-                                send_discord_msg!(DiscordClientRequest::clear());
-                            }, // sometimes it's none, probably because the pointer indirection changes during the asynchronous memory requests
-                            _ => {}
-                        }
-                    // Else, we want to see if the current game mode is enabled in the config (we're in-game)
-                    } else if match gamemode {
-                        MeleeScene::SlippiCss(_) => false, // if we are in css, ignore
-                        MeleeScene::SlippiOnline(scene) => c.slippi.enabled &&
-                            scene.and_then(|s| Some(s.is_enabled(c))).unwrap_or(true),
-                        MeleeScene::UnclePunch => c.uncle_punch.enabled,
-                        MeleeScene::TrainingMode => c.training_mode.enabled,
-                        MeleeScene::VsMode => c.vs_mode.enabled,
-                        MeleeScene::HomeRunContest => c.stadium.enabled && c.stadium.hrc.enabled,
-                        MeleeScene::TargetTest(_) => c.stadium.enabled && c.stadium.btt.enabled,
-                        MeleeScene::MultiManMelee(_) => c.stadium.enabled && c.stadium.mmm.enabled
-                    } {
-                        let game_time = self.game_time();
-                        let timestamp = if c.global.show_in_game_time {
-                            DiscordClientRequestTimestamp {
-                                mode: match self.timer_mode() {
-                                    TimerMode::Countdown => DiscordClientRequestTimestampMode::End,
-                                    TimerMode::Frozen => DiscordClientRequestTimestampMode::Static,
-                                    _ => DiscordClientRequestTimestampMode::Start
-                                },
-                                timestamp: if self.timer_mode() == TimerMode::Countdown { current_unix_time() + game_time } else { current_unix_time() - game_time }
-                            }
-                        } else {
-                            DiscordClientRequestTimestamp::none()
-                        };
-                        let player_index = match gamemode {
-                            MeleeScene::VsMode => self.get_player_port(),
-                            MeleeScene::SlippiOnline(_) => self.get_slippi_player_port(),
-                            _ => Some(0u8) // default to port 1, mostly the case in single player modes like training mode/unclepunch
-                        }.unwrap_or(0u8);
-                        let request = DiscordClientRequest::game(
-                            match gamemode { MeleeScene::TargetTest(scene) => scene, _ => self.get_stage() },
-                            if c.global.show_in_game_character { self.get_character(player_index) } else { Some(MeleeCharacter::Hidden) },
-                            gamemode,
-                            timestamp,
-                            if match gamemode { MeleeScene::SlippiOnline(_) => true, _ => false } && c.slippi.show_opponent_name { self.get_opp_name() } else { None }
-                        );
-                        
-                        send_discord_msg!(request.clone());
-                    } else {
-                        send_discord_msg!(DiscordClientRequest::clear());
-                    }
-                } else if self.last_payload.req_type != DiscordClientRequestType::Clear {
-                    send_discord_msg!(DiscordClientRequest::clear());
-                }
-            });
+        let observation = self.observe();
+        let mut events = self.scene_state.update(observation.as_ref());
+        if let Some((i, result)) = events.iter().enumerate().find_map(|(i, event)| self.game_result(event).map(|result| (i, result))) {
+            events.insert(i + 1, result);
+        }
+        for event in events {
+            // the payload of a new game might look like the one of the last game, send it regardless
+            if let MeleeEvent::GameStarted(_) = event {
+                self.last_payload = DiscordClientRequest::clear();
+            }
+            self.session.handle(&event, self.mem.current_time());
+            self.update_ranked_set(&event);
+            self.update_record_baseline(&event);
+            self.update_attempt(&event);
+            outputs.push(MeleeClientOutput::Event(event));
+        }
 
-            sleep(RUN_INTERVAL);
+        if let Some(request) = CONFIG.with_ref(|c| self.presence_request(c)) {
+            let now = self.mem.current_time();
+            if self.last_payload != request && !(self.last_payload.only_live_state_differs(&request) && now - self.last_payload_time < LIVE_UPDATE_INTERVAL) {
+                self.last_payload = request.clone();
+                self.last_payload_time = now;
+                outputs.push(MeleeClientOutput::Presence(Box::new(request)));
+            }
         }
+        outputs
     }
 }
\ No newline at end of file
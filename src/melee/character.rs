@@ -66,6 +66,16 @@ impl MeleeCharacter {
 			_ => None
 		}
 	}
+
+	// how many colors the character can pick on the css, the first one is the default
+	pub fn costume_count(&self) -> u8 {
+		match self {
+			Self::CaptainFalcon | Self::Kirby | Self::Yoshi => 6,
+			Self::Fox | Self::Falco | Self::Bowser | Self::Luigi | Self::Mewtwo | Self::Ness | Self::Pikachu | Self::Pichu | Self::IceClimbers | Self::MrGameAndWatch => 4,
+			Self::Hidden => 0,
+			_ => 5
+		}
+	}
 }
 
 impl Display for MeleeCharacter {
@@ -103,12 +113,18 @@ impl Display for MeleeCharacter {
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub struct OptionalMeleeCharacter(pub Option<MeleeCharacter>);
+pub struct OptionalMeleeCharacter(pub Option<MeleeCharacter>, pub Option<u8>); // character, costume
 impl OptionalMeleeCharacter {
+	// char{id}_{costume} for the colors, the default color and unknown costumes use the base asset
 	pub fn as_discord_resource(&self) -> String {
 		self.0.as_ref().and_then(|c|
 			if *c == MeleeCharacter::Hidden { Some("transparent".into()) }
-			else { Some(format!("char{}", (*c) as u8) ) }
+			else {
+				match self.1.filter(|costume| *costume > 0 && *costume < c.costume_count()) {
+					Some(costume) => Some(format!("char{}_{}", (*c) as u8, costume)),
+					None => Some(format!("char{}", (*c) as u8))
+				}
+			}
 		).unwrap_or("questionmark".into())
 	}
 }
@@ -0,0 +1,59 @@
+// The GameCube is big endian, so everything we read from its memory goes through this instead of transmuting raw bytes.
+pub trait FromBigEndian: Sized {
+    const SIZE: usize;
+    // bytes is exactly SIZE long
+    fn from_be_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_from_big_endian {
+    ($($t:ty),*) => {
+        $(
+            impl FromBigEndian for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+                fn from_be_bytes(bytes: &[u8]) -> Self {
+                    <$t>::from_be_bytes(bytes.try_into().unwrap())
+                }
+            }
+        )*
+    };
+}
+impl_from_big_endian!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+impl FromBigEndian for bool {
+    const SIZE: usize = 1;
+    fn from_be_bytes(bytes: &[u8]) -> Self {
+        bytes[0] != 0
+    }
+}
+
+impl<T: FromBigEndian, const N: usize> FromBigEndian for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+    fn from_be_bytes(bytes: &[u8]) -> Self {
+        std::array::from_fn(|i| T::from_be_bytes(&bytes[i * T::SIZE..(i + 1) * T::SIZE]))
+    }
+}
+
+// Declares a struct whose fields are laid out back to back in memory, use byte arrays for padding/unknown fields.
+macro_rules! big_endian_struct {
+    ($(#[$meta:meta])* $vis:vis struct $name:ident { $($(#[$fmeta:meta])* $fvis:vis $field:ident: $ty:ty),* $(,)? }) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$fmeta])* $fvis $field: $ty),*
+        }
+
+        impl $crate::melee::dolphin_mem::FromBigEndian for $name {
+            const SIZE: usize = 0 $(+ <$ty as $crate::melee::dolphin_mem::FromBigEndian>::SIZE)*;
+            fn from_be_bytes(bytes: &[u8]) -> Self {
+                let mut offset = 0;
+                $(
+                    let size = <$ty as $crate::melee::dolphin_mem::FromBigEndian>::SIZE;
+                    let $field = <$ty as $crate::melee::dolphin_mem::FromBigEndian>::from_be_bytes(&bytes[offset..offset + size]);
+                    offset += size;
+                )*
+                let _ = offset;
+                $name { $($field),* }
+            }
+        }
+    };
+}
+pub(crate) use big_endian_struct;
@@ -0,0 +1,76 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::MemorySource;
+
+// Serves reads from a raw MEM1 dump (e.g. Dolphin's "Dump MRAM"), where byte 0 corresponds to 0x80000000.
+pub struct MemoryDump {
+    data: Vec<u8>
+}
+
+impl MemoryDump {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::from_bytes(fs::read(path)?))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        MemoryDump { data }
+    }
+}
+
+impl MemorySource for MemoryDump {
+    // a dump is always "attached"
+    fn find_process(&mut self) -> bool { true }
+    fn has_process(&self) -> bool { true }
+    fn check_process_running(&mut self) -> bool { true }
+
+    fn read_raw(&mut self, offset: u32, output: &mut [u8]) -> bool {
+        let start = offset as usize;
+        match self.data.get(start..start + output.len()) {
+            Some(bytes) => {
+                output.copy_from_slice(bytes);
+                true
+            },
+            None => {
+                println!("[MEMORY] Address {:#08X} is out of bounds of the memory dump", offset);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::AppConfig, discord::DiscordClientRequestType, melee::{character::MeleeCharacter, fixtures::TestRam, stage::MeleeStage, MeleeClient, MeleeScene}};
+
+    use super::{super::GC_RAM_SIZE, MemoryDump};
+
+    // a 4-stock Vs. Mode game on Battlefield, Fox (port 1) against a level 9 Marth CPU (port 2)
+    fn vs_mode_dump() -> MemoryDump {
+        let mut ram = TestRam::new(GC_RAM_SIZE);
+        ram.scene(2, 2).stage(MeleeStage::Battle).local_port(0);
+        // stock match with a countdown timer, 8 minutes, no items, 4 stocks
+        ram.match_init(0x00, &[0x20 | 0x02]).match_init(0x0B, &[0xFF]).match_init(0x10, &480u32.to_be_bytes()).match_init(0x60 + 0x02, &[4]);
+        ram.player(0, MeleeCharacter::Fox, 4).costume(0, 1);
+        ram.player(1, MeleeCharacter::Marth, 4).cpu(1, 9);
+        MemoryDump::from_bytes(ram.build())
+    }
+
+    #[test]
+    fn presence_from_dump() {
+        let mut client = MeleeClient::with_source(vs_mode_dump());
+        assert_eq!(client.detect_game().map(|g| g.id), Some("GALE01".into()));
+        assert_eq!(client.get_melee_scene(), Some(MeleeScene::VsMode));
+        assert_eq!(client.get_stage(), Some(MeleeStage::Battle));
+        assert_eq!(client.get_character(0), Some(MeleeCharacter::Fox));
+
+        let request = client.presence_request(&AppConfig::default()).unwrap();
+        assert_eq!(request.req_type, DiscordClientRequestType::Game);
+        assert_eq!(request.mode, "Vs. Mode");
+        assert_eq!(request.stage.0, Some(MeleeStage::Battle));
+        assert_eq!((request.character.0, request.character.1), (Some(MeleeCharacter::Fox), Some(1)));
+        assert_eq!(request.matchup.map(|m| m.to_string()), Some("Fox vs Lv. 9 Marth (CPU)".into()));
+        assert_eq!(request.rules.map(|r| r.to_string()), Some("4-stock, 8:00, items off".into()));
+    }
+}
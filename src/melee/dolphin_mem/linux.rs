@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use libc::{iovec, pid_t, process_vm_readv};
+
+use super::{DolphinInstance, GC_RAM_SIZE, MemorySource, parse_game_id};
+
+// comm is truncated to 15 characters, so we also compare against the executable name in cmdline
+const COMM_LEN: usize = 15;
+const VALID_PROCESS_NAMES: &'static [&'static str] = &["dolphin-emu", "Slippi_Online", "Slippi_Playback", "Slippi Dolphin"];
+// Dolphin backs the emulated RAM with a shared memory file, e.g. "/dev/shm/dolphin-emu.1234" or "/memfd:dolphin-emu.1234 (deleted)"
+const RAM_MAPPING_NAME: &str = "dolphin-emu";
+
+pub struct DolphinMemory {
+    pid: Option<pid_t>,
+    dolphin_base_addr: Option<usize>,
+    dolphin_addr_size: Option<usize>,
+    // so switching between instances doesn't look up their mapping again every time
+    known_mappings: HashMap<pid_t, (usize, usize)>,
+    permission_reported: bool
+}
+
+impl DolphinMemory {
+    pub fn new() -> Self {
+        DolphinMemory { pid: None, dolphin_base_addr: None, dolphin_addr_size: None, known_mappings: HashMap::new(), permission_reported: false }
+    }
+
+    fn find_gamecube_ram_offset(&mut self) -> bool {
+        if !self.has_process() {
+            return false;
+        }
+
+        if let Some(mapping) = find_ram_mapping(self.pid.unwrap()) {
+            self.set_ram_mapping(mapping);
+            return true;
+        }
+        return false;
+    }
+
+    fn set_ram_mapping(&mut self, (base_addr, size): (usize, usize)) {
+        self.dolphin_base_addr = Some(base_addr);
+        self.dolphin_addr_size = Some(size);
+
+        if self.known_mappings.insert(self.pid.unwrap(), (base_addr, size)) != Some((base_addr, size)) {
+            println!("Dolphin Base Address: {:#X}", base_addr);
+            println!("Dolphin Address Size: {:?}", self.dolphin_addr_size);
+        }
+    }
+
+    fn has_gamecube_ram_offset(&self) -> bool {
+        self.dolphin_base_addr.is_some()
+    }
+
+    fn reset(&mut self) {
+        self.pid = None;
+        self.dolphin_base_addr = None;
+        self.dolphin_addr_size = None;
+    }
+
+    // reading another process' memory needs ptrace access to it, which most distros restrict to its parent by default
+    fn report_permission_denied(&mut self, pid: pid_t) {
+        if self.permission_reported {
+            return;
+        }
+        self.permission_reported = true;
+        let exe = std::env::current_exe().map(|p| p.display().to_string()).unwrap_or("<path to this program>".into());
+        println!("[MEMORY] Not allowed to read the memory of Dolphin (pid {}). This is usually kernel.yama.ptrace_scope being set to 1.", pid);
+        println!("[MEMORY] Either allow it for this program with `sudo setcap cap_sys_ptrace=eip {}`", exe);
+        println!("[MEMORY] or for every process until the next reboot with `sudo sysctl kernel.yama.ptrace_scope=0`");
+    }
+}
+
+impl MemorySource for DolphinMemory {
+    fn find_process(&mut self) -> bool {
+        // the AppImage runtime shows up next to the actual emulator process, only the latter maps the emulated RAM
+        for pid in dolphin_processes() {
+            if self.attach(pid as u32) {
+                println!("{}", process_name(pid).unwrap_or_default());
+                break;
+            }
+        }
+        self.has_process()
+    }
+
+    fn has_process(&self) -> bool {
+        self.pid.is_some()
+    }
+
+    fn instances(&mut self) -> Vec<DolphinInstance> {
+        dolphin_processes().into_iter()
+            // skip the AppImage runtime if the emulator process next to it maps the emulated RAM
+            .map(|pid| (pid, find_ram_mapping(pid)))
+            .filter(|(pid, mapping)| mapping.is_some() || !is_appimage_runtime(*pid))
+            .map(|(pid, mapping)| DolphinInstance {
+                pid: pid as u32,
+                exe: executable_path(pid).unwrap_or_default(),
+                game_id: mapping.and_then(|(base_addr, _)| read_game_id(pid, base_addr))
+            })
+            .collect()
+    }
+
+    fn attach(&mut self, pid: u32) -> bool {
+        self.reset();
+        let pid = pid as pid_t;
+        if !is_dolphin_process(pid) {
+            return false;
+        }
+        // without the emulated RAM there's nothing to read, and we would never look for the process that has it
+        let mapping = match self.known_mappings.get(&pid).copied().or_else(|| find_ram_mapping(pid)) {
+            Some(mapping) => mapping,
+            None => return false
+        };
+        // finding the mapping only takes /proc, reading it needs ptrace access
+        if let Err(err) = read_remote(pid, mapping.0, &mut [0u8; 1]) {
+            if err.kind() == io::ErrorKind::PermissionDenied {
+                self.report_permission_denied(pid);
+                return false;
+            }
+        }
+        self.pid = Some(pid);
+        self.set_ram_mapping(mapping);
+        true
+    }
+
+    fn attached_pid(&self) -> Option<u32> {
+        self.pid.map(|pid| pid as u32)
+    }
+
+    fn check_process_running(&mut self) -> bool {
+        if self.pid.is_none() {
+            return false;
+        }
+
+        if !Path::new(&format!("/proc/{}", self.pid.unwrap())).exists() {
+            self.reset();
+            return false;
+        }
+        return true;
+    }
+
+    fn read_raw(&mut self, addr: u32, output: &mut [u8]) -> bool {
+        if !self.has_gamecube_ram_offset() && !self.find_gamecube_ram_offset() {
+            return false;
+        }
+
+        let pid = self.pid.unwrap();
+        let err = match read_remote(pid, self.dolphin_base_addr.unwrap() + addr as usize, output) {
+            Ok(()) => return true,
+            Err(err) => err
+        };
+
+        // e.g. the capability was taken away, this won't get better by reading again
+        if err.kind() == io::ErrorKind::PermissionDenied {
+            self.report_permission_denied(pid);
+            self.reset();
+            return false;
+        }
+        println!("[MEMORY] Failed reading from address {:#08X} ERROR {}", addr, err);
+        if matches!(err.raw_os_error(), None | Some(libc::EFAULT) | Some(libc::ESRCH)) { // game probably closed, reset the dolphin ram offset
+            self.dolphin_addr_size = None;
+            self.dolphin_base_addr = None;
+            self.known_mappings.remove(&pid);
+        }
+        return false;
+    }
+}
+
+fn dolphin_processes() -> Vec<pid_t> {
+    match fs::read_dir("/proc") {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().and_then(|e| e.file_name().to_str().and_then(|n| n.parse::<pid_t>().ok())))
+            .filter(|&pid| is_dolphin_process(pid))
+            .collect(),
+        Err(_) => Vec::new()
+    }
+}
+
+fn process_name(pid: pid_t) -> Option<String> {
+    // first argument in cmdline is the full executable path, comm is the (truncated) fallback
+    let exe = fs::read(format!("/proc/{}/cmdline", pid)).ok().and_then(|cmdline| {
+        cmdline.split(|&b| b == 0).next()
+            .and_then(|arg| Path::new(&*String::from_utf8_lossy(arg)).file_name().and_then(|n| n.to_str().map(String::from)))
+            .filter(|n| !n.is_empty())
+    });
+    exe.or_else(|| fs::read_to_string(format!("/proc/{}/comm", pid)).ok().map(|c| c.trim_end().to_string()))
+}
+
+fn is_dolphin_process(pid: pid_t) -> bool {
+    let comm = fs::read_to_string(format!("/proc/{}/comm", pid)).unwrap_or_default();
+    let comm = comm.trim_end();
+    let exe = process_name(pid).unwrap_or_default();
+    VALID_PROCESS_NAMES.iter().any(|&e| exe.starts_with(e) || (!comm.is_empty() && &e[..e.len().min(COMM_LEN)] == comm))
+}
+
+// AppImages run from a temporary mount, APPIMAGE holds the path the user actually launched
+fn executable_path(pid: pid_t) -> Option<String> {
+    let appimage = fs::read(format!("/proc/{}/environ", pid)).ok().and_then(|environ| {
+        environ.split(|&b| b == 0)
+            .find_map(|var| var.strip_prefix(b"APPIMAGE=").map(|v| String::from_utf8_lossy(v).into_owned()))
+    });
+    appimage.or_else(|| fs::read_link(format!("/proc/{}/exe", pid)).ok().map(|p| p.to_string_lossy().into_owned()))
+}
+
+// the runtime is the parent of the actual emulator process
+fn is_appimage_runtime(pid: pid_t) -> bool {
+    dolphin_processes().iter().any(|&other| other != pid && parent_pid(other) == Some(pid))
+}
+
+fn parent_pid(pid: pid_t) -> Option<pid_t> {
+    // format: pid (comm) state ppid ..., comm may contain spaces
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    stat.rsplit_once(')')?.1.split_whitespace().nth(1)?.parse::<pid_t>().ok()
+}
+
+// fills output from addr in the process, a short read is an error without an os error code
+fn read_remote(pid: pid_t, addr: usize, output: &mut [u8]) -> io::Result<()> {
+    let size = output.len();
+    let local = iovec { iov_base: output.as_mut_ptr() as *mut c_void, iov_len: size };
+    let remote = iovec { iov_base: addr as *mut c_void, iov_len: size };
+    let memread = unsafe { process_vm_readv(pid, &local, 1, &remote, 1, 0) };
+    if memread < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if memread as usize != size {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("read {} of {} bytes", memread, size)));
+    }
+    Ok(())
+}
+
+fn read_game_id(pid: pid_t, base_addr: usize) -> Option<String> {
+    let mut raw = [0u8; 6];
+    read_remote(pid, base_addr, &mut raw).ok()?;
+    parse_game_id(&raw)
+}
+
+fn find_ram_mapping(pid: pid_t) -> Option<(usize, usize)> {
+    let maps = fs::read_to_string(format!("/proc/{}/maps", pid)).ok()?;
+    for line in maps.lines() {
+        // format: start-end perms offset dev inode [path]
+        let mut fields = line.split_whitespace();
+        let (range, perms, offset) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(range), Some(perms), Some(offset)) => (range, perms, offset),
+            _ => continue
+        };
+        let path = fields.skip(2).collect::<Vec<_>>().join(" ");
+        if !path.contains(RAM_MAPPING_NAME) || !perms.starts_with('r') || usize::from_str_radix(offset, 16) != Ok(0) {
+            continue;
+        }
+
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (usize::from_str_radix(start, 16), usize::from_str_radix(end, 16)),
+            None => continue
+        };
+        if let (Ok(start), Ok(end)) = (start, end) {
+            let size = end - start;
+            // Dolphin stores the GameCube RAM address space in 32MB chunks.
+            // Extended memory override can allow up to 64MB.
+            if size >= GC_RAM_SIZE && size % GC_RAM_SIZE == 0 {
+                return Some((start, size));
+            }
+        }
+    }
+    None
+}
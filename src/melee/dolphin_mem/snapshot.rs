@@ -0,0 +1,73 @@
+use super::{DolphinInstance, MemorySource, GC_RAM_START, GC_RAM_END};
+
+// Caches whole memory regions for the duration of one tick. Reads that fall inside of a captured region are served
+// from the cache, so we only issue one read per region and all values of one presence update are consistent.
+pub struct SnapshotMemory<M: MemorySource> {
+    inner: M,
+    regions: Vec<(u32, Vec<u8>)>
+}
+
+impl<M: MemorySource> SnapshotMemory<M> {
+    pub fn new(inner: M) -> Self {
+        SnapshotMemory { inner, regions: Vec::new() }
+    }
+
+    // reads addr..addr + len in one go, reads inside of it are served from the snapshot until it's cleared
+    pub fn capture(&mut self, addr: u32, len: usize) -> bool {
+        if !self.inner.has_process() || addr < GC_RAM_START || addr as u64 + len as u64 > GC_RAM_END as u64 {
+            return false;
+        }
+
+        let offset = addr % GC_RAM_START;
+        let mut bytes = vec![0u8; len];
+        if !self.inner.read_raw(offset, &mut bytes) {
+            return false;
+        }
+        self.regions.push((offset, bytes));
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.regions.clear();
+    }
+}
+
+impl<M: MemorySource> MemorySource for SnapshotMemory<M> {
+    fn find_process(&mut self) -> bool { self.inner.find_process() }
+    fn has_process(&self) -> bool { self.inner.has_process() }
+    fn check_process_running(&mut self) -> bool { self.inner.check_process_running() }
+    fn instances(&mut self) -> Vec<DolphinInstance> { self.inner.instances() }
+    fn attach(&mut self, pid: u32) -> bool {
+        // the snapshot belongs to the previous process
+        self.clear();
+        self.inner.attach(pid)
+    }
+    fn attached_pid(&self) -> Option<u32> { self.inner.attached_pid() }
+
+    fn read_raw(&mut self, offset: u32, output: &mut [u8]) -> bool {
+        let start = offset as usize;
+        let cached = self.regions.iter().find_map(|(region_offset, bytes)| {
+            let region_start = *region_offset as usize;
+            if start >= region_start && start + output.len() <= region_start + bytes.len() {
+                Some(&bytes[start - region_start..start - region_start + output.len()])
+            } else {
+                None
+            }
+        });
+        match cached {
+            Some(bytes) => {
+                output.copy_from_slice(bytes);
+                true
+            },
+            None => self.inner.read_raw(offset, output)
+        }
+    }
+
+    fn next_tick(&mut self, interval: u64) {
+        self.clear();
+        self.inner.next_tick(interval);
+    }
+
+    fn current_time(&self) -> i64 { self.inner.current_time() }
+    fn is_exhausted(&self) -> bool { self.inner.is_exhausted() }
+}
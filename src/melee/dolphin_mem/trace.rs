@@ -0,0 +1,404 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+use super::{DolphinInstance, MemorySource};
+
+// Trace file layout (little endian):
+// header: b"SRPT" + version (u8)
+// entries: kind (u8), followed by
+//   TICK_ENTRY: tick (u32, starts at 0 whenever a recorder starts), unix time (i64)
+//   READ_ENTRY: offset (u32), size (u16), raw bytes (size)
+//   PATCH_ENTRY: offset (u32), size (u16), start (u16), length (u16), raw bytes (length)
+//   SAME_ENTRY: offset (u32), size (u16)
+//   PROCESS_ENTRY: attached to a process (u8)
+// A read is written in full the first time (and again after the process changed or the recorder restarted), after that
+// only the bytes that changed since it was last recorded are patched in. Reads that didn't change at all are only marked,
+// so the replay knows they happened in that tick.
+const TRACE_MAGIC: &[u8; 4] = b"SRPT";
+const TRACE_VERSION: u8 = 2;
+const TICK_ENTRY: u8 = 0;
+const READ_ENTRY: u8 = 1;
+const PROCESS_ENTRY: u8 = 2;
+const PATCH_ENTRY: u8 = 3;
+const SAME_ENTRY: u8 = 4;
+// changed bytes closer than this end up in the same patch, every patch costs 11 bytes
+const PATCH_GAP: usize = 8;
+
+// Wraps a memory source and appends every successful read to a trace file, so a session can be replayed with TraceReplay
+pub struct TraceRecorder<M: MemorySource> {
+    inner: M,
+    writer: BufWriter<File>,
+    tick: u32,
+    recorded: HashMap<(u32, usize), Vec<u8>> // the bytes of every read as the replay will know them
+}
+
+impl<M: MemorySource> TraceRecorder<M> {
+    pub fn create<P: AsRef<Path>>(path: P, inner: M) -> io::Result<Self> {
+        // we append, so a restarted client (e.g. after a crash) continues the same trace
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(path)?;
+        let is_empty = file.metadata()?.len() == 0;
+        if !is_empty {
+            let mut header = [0u8; 5];
+            file.read_exact(&mut header)?;
+            if &header[0..4] != TRACE_MAGIC || header[4] != TRACE_VERSION {
+                return Err(invalid("not a trace file of this version"));
+            }
+        }
+        let mut writer = BufWriter::new(file);
+        if is_empty {
+            writer.write_all(TRACE_MAGIC)?;
+            writer.write_all(&[TRACE_VERSION])?;
+        }
+        let mut recorder = TraceRecorder { inner, writer, tick: 0, recorded: HashMap::new() };
+        recorder.write_tick()?;
+        Ok(recorder)
+    }
+
+    fn write_tick(&mut self) -> io::Result<()> {
+        self.writer.write_all(&[TICK_ENTRY])?;
+        self.writer.write_all(&self.tick.to_le_bytes())?;
+        self.writer.write_all(&self.inner.current_time().to_le_bytes())?;
+        self.writer.flush()
+    }
+
+    fn write_process(&mut self) -> io::Result<()> {
+        // whatever we attach to next starts with full reads again
+        if !self.inner.has_process() {
+            self.recorded.clear();
+        }
+        self.writer.write_all(&[PROCESS_ENTRY, self.inner.has_process() as u8])
+    }
+
+    fn log_error(res: io::Result<()>) {
+        if let Err(err) = res {
+            println!("[TRACE] Failed writing to trace file: {}", err);
+        }
+    }
+
+    fn write_read(&mut self, offset: u32, bytes: &[u8]) -> io::Result<()> {
+        let key = (offset, bytes.len());
+        let previous = match self.recorded.get_mut(&key) {
+            Some(previous) => previous,
+            None => {
+                self.writer.write_all(&[READ_ENTRY])?;
+                self.writer.write_all(&offset.to_le_bytes())?;
+                self.writer.write_all(&(bytes.len() as u16).to_le_bytes())?;
+                self.writer.write_all(bytes)?;
+                self.recorded.insert(key, bytes.to_vec());
+                return Ok(());
+            }
+        };
+
+        let patches = changed_ranges(previous, bytes);
+        if patches.is_empty() {
+            self.writer.write_all(&[SAME_ENTRY])?;
+            self.writer.write_all(&offset.to_le_bytes())?;
+            return self.writer.write_all(&(bytes.len() as u16).to_le_bytes());
+        }
+        for (start, end) in patches {
+            self.writer.write_all(&[PATCH_ENTRY])?;
+            self.writer.write_all(&offset.to_le_bytes())?;
+            self.writer.write_all(&(bytes.len() as u16).to_le_bytes())?;
+            self.writer.write_all(&(start as u16).to_le_bytes())?;
+            self.writer.write_all(&((end - start) as u16).to_le_bytes())?;
+            self.writer.write_all(&bytes[start..end])?;
+        }
+        previous.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+// start..end of every run of bytes that differs between the two, runs with a small gap in between are merged
+fn changed_ranges(old: &[u8], new: &[u8]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for i in (0..new.len()).filter(|i| old[*i] != new[*i]) {
+        match ranges.last_mut() {
+            Some((_, end)) if i - *end < PATCH_GAP => *end = i + 1,
+            _ => ranges.push((i, i + 1))
+        }
+    }
+    ranges
+}
+
+impl<M: MemorySource> MemorySource for TraceRecorder<M> {
+    fn find_process(&mut self) -> bool {
+        let found = self.inner.find_process();
+        Self::log_error(self.write_process());
+        found
+    }
+    fn has_process(&self) -> bool { self.inner.has_process() }
+    fn check_process_running(&mut self) -> bool {
+        let running = self.inner.check_process_running();
+        Self::log_error(self.write_process());
+        running
+    }
+    fn instances(&mut self) -> Vec<DolphinInstance> { self.inner.instances() }
+    fn attach(&mut self, pid: u32) -> bool {
+        let attached = self.inner.attach(pid);
+        // another process, nothing we recorded so far applies to it
+        self.recorded.clear();
+        Self::log_error(self.write_process());
+        attached
+    }
+    fn attached_pid(&self) -> Option<u32> { self.inner.attached_pid() }
+
+    fn read_raw(&mut self, offset: u32, output: &mut [u8]) -> bool {
+        let success = self.inner.read_raw(offset, output);
+        if success {
+            Self::log_error(self.write_read(offset, output));
+        }
+        success
+    }
+
+    fn next_tick(&mut self, interval: u64) {
+        self.inner.next_tick(interval);
+        // a tick without data would replay as one more tick than the client actually ran
+        if self.inner.is_exhausted() {
+            return;
+        }
+        self.tick += 1;
+        Self::log_error(self.write_tick());
+    }
+
+    fn current_time(&self) -> i64 { self.inner.current_time() }
+    fn is_exhausted(&self) -> bool { self.inner.is_exhausted() }
+}
+
+enum TraceRead {
+    Full(Vec<u8>),
+    Patch { start: usize, bytes: Vec<u8> },
+    Same
+}
+
+struct TraceTick {
+    time: i64,
+    attached: Option<bool>,
+    reads: Vec<((u32, usize), TraceRead)>
+}
+
+// Serves the reads of a recorded trace tick by tick on a simulated clock
+pub struct TraceReplay {
+    ticks: Vec<TraceTick>,
+    current: usize,
+    attached: bool,
+    recorded: HashMap<(u32, usize), Vec<u8>>, // every read as of the current tick
+    read_now: HashSet<(u32, usize)> // the reads that happened during the current tick
+}
+
+impl TraceReplay {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 5 || &data[0..4] != TRACE_MAGIC {
+            return Err(invalid("not a trace file"));
+        }
+        if data[4] != TRACE_VERSION {
+            return Err(invalid("unsupported trace version"));
+        }
+
+        let mut ticks: Vec<TraceTick> = Vec::new();
+        let mut pos = 5;
+        while pos < data.len() {
+            let kind = take(data, &mut pos, 1)?[0];
+            if kind == TICK_ENTRY {
+                let _tick = u32::from_le_bytes(take(data, &mut pos, 4)?.try_into().unwrap());
+                let time = i64::from_le_bytes(take(data, &mut pos, 8)?.try_into().unwrap());
+                ticks.push(TraceTick { time, attached: None, reads: Vec::new() });
+                continue;
+            }
+            let tick = ticks.last_mut().ok_or_else(|| invalid("entry before the first tick"))?;
+            if kind == PROCESS_ENTRY {
+                tick.attached = Some(take(data, &mut pos, 1)?[0] != 0);
+                continue;
+            }
+
+            let offset = u32::from_le_bytes(take(data, &mut pos, 4)?.try_into().unwrap());
+            let size = u16::from_le_bytes(take(data, &mut pos, 2)?.try_into().unwrap()) as usize;
+            let read = match kind {
+                READ_ENTRY => TraceRead::Full(take(data, &mut pos, size)?.to_vec()),
+                PATCH_ENTRY => {
+                    let start = u16::from_le_bytes(take(data, &mut pos, 2)?.try_into().unwrap()) as usize;
+                    let len = u16::from_le_bytes(take(data, &mut pos, 2)?.try_into().unwrap()) as usize;
+                    if start + len > size {
+                        return Err(invalid("patch out of bounds of its read"));
+                    }
+                    TraceRead::Patch { start, bytes: take(data, &mut pos, len)?.to_vec() }
+                },
+                SAME_ENTRY => TraceRead::Same,
+                _ => return Err(invalid("unknown trace entry"))
+            };
+            tick.reads.push(((offset, size), read));
+        }
+
+        let mut replay = TraceReplay { ticks, current: 0, attached: false, recorded: HashMap::new(), read_now: HashSet::new() };
+        replay.apply_tick()?;
+        Ok(replay)
+    }
+
+    fn tick(&self) -> Option<&TraceTick> {
+        self.ticks.get(self.current)
+    }
+
+    // brings the reads up to date with the current tick
+    fn apply_tick(&mut self) -> io::Result<()> {
+        self.read_now.clear();
+        let tick = match self.ticks.get(self.current) {
+            Some(tick) => tick,
+            None => return Ok(())
+        };
+        for (key, read) in &tick.reads {
+            match read {
+                TraceRead::Full(bytes) => { self.recorded.insert(*key, bytes.clone()); },
+                TraceRead::Patch { start, bytes } => {
+                    let recorded = self.recorded.get_mut(key).ok_or_else(|| invalid("patch of a read that wasn't recorded"))?;
+                    recorded[*start..*start + bytes.len()].copy_from_slice(bytes);
+                },
+                TraceRead::Same => if !self.recorded.contains_key(key) {
+                    return Err(invalid("repeat of a read that wasn't recorded"));
+                }
+            }
+            self.read_now.insert(*key);
+        }
+        Ok(())
+    }
+
+    // the process state as it was recorded during the current tick
+    fn sync_process(&mut self) -> bool {
+        if let Some(attached) = self.tick().and_then(|t| t.attached) {
+            self.attached = attached;
+        }
+        self.attached
+    }
+}
+
+impl MemorySource for TraceReplay {
+    fn find_process(&mut self) -> bool { self.sync_process() }
+    fn has_process(&self) -> bool { self.attached }
+    fn check_process_running(&mut self) -> bool { self.sync_process() }
+
+    fn read_raw(&mut self, offset: u32, output: &mut [u8]) -> bool {
+        let key = (offset, output.len());
+        match self.recorded.get(&key).filter(|_| self.read_now.contains(&key)) {
+            Some(bytes) => {
+                output.copy_from_slice(bytes);
+                true
+            },
+            None => false
+        }
+    }
+
+    fn next_tick(&mut self, _interval: u64) {
+        // simulated clock, we don't actually wait
+        self.current += 1;
+        if let Err(err) = self.apply_tick() {
+            println!("[TRACE] Stopping the replay at tick {}: {}", self.current, err);
+            self.current = self.ticks.len();
+        }
+    }
+
+    fn current_time(&self) -> i64 {
+        self.tick().or(self.ticks.last()).map(|t| t.time).unwrap_or(0)
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.current >= self.ticks.len()
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> io::Result<&'a [u8]> {
+    let bytes = data.get(*pos..*pos + len).ok_or_else(|| invalid("unexpected end of trace"))?;
+    *pos += len;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{discord::DiscordClientRequestType, melee::{character::MeleeCharacter, fixtures::TestRam, MeleeClient, MeleeClientOutput, MeleeEvent, MeleeScene}};
+
+    use super::{MemorySource, TraceRecorder, TraceReplay};
+
+    const RAM_LEN: usize = 0x500000;
+
+    // one memory image per tick
+    struct ScriptedMemory {
+        ticks: Vec<Vec<u8>>,
+        current: usize
+    }
+
+    impl MemorySource for ScriptedMemory {
+        fn find_process(&mut self) -> bool { true }
+        fn has_process(&self) -> bool { true }
+        fn check_process_running(&mut self) -> bool { true }
+        fn read_raw(&mut self, offset: u32, output: &mut [u8]) -> bool {
+            let start = offset as usize;
+            match self.ticks.get(self.current).and_then(|mem| mem.get(start..start + output.len())) {
+                Some(bytes) => {
+                    output.copy_from_slice(bytes);
+                    true
+                },
+                None => false
+            }
+        }
+        fn next_tick(&mut self, _interval: u64) { self.current += 1; }
+        fn current_time(&self) -> i64 { 1_700_000_000 + self.current as i64 }
+        fn is_exhausted(&self) -> bool { self.current >= self.ticks.len() }
+    }
+
+    // Vs. Mode: the character select screen, a few seconds of Fox against Marth and the character select screen again
+    fn vs_mode_ticks() -> Vec<Vec<u8>> {
+        (0..6u32).map(|tick| {
+            let mut ram = TestRam::new(RAM_LEN);
+            if (1..5).contains(&tick) {
+                ram.scene(2, 2).game_time(tick - 1).match_init(0x00, &[0x03]); // counting up
+                ram.player(0, MeleeCharacter::Fox, 4).player(1, MeleeCharacter::Marth, 4);
+            } else {
+                ram.scene(2, 0);
+            }
+            ram.build()
+        }).collect()
+    }
+
+    // runs the client on the ticks while recording, returns what it produced and the trace
+    fn record(ticks: Vec<Vec<u8>>, name: &str) -> (Vec<MeleeClientOutput>, Vec<u8>) {
+        let path = std::env::temp_dir().join(format!("slippi-rich-presence-{}-{}.trace", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        let outputs = {
+            let recorder = TraceRecorder::create(&path, ScriptedMemory { ticks, current: 0 }).unwrap();
+            MeleeClient::with_source(recorder).replay()
+        };
+        let trace = fs::read(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        (outputs, trace)
+    }
+
+    #[test]
+    fn replay_matches_recording() {
+        let (recorded, trace) = record(vs_mode_ticks(), "session");
+        let replayed = MeleeClient::with_source(TraceReplay::from_bytes(&trace).unwrap()).replay();
+
+        assert_eq!(replayed, recorded);
+        let started = replayed.iter().position(|o| *o == MeleeClientOutput::Event(MeleeEvent::GameStarted(MeleeScene::VsMode))).unwrap();
+        match &replayed[started + 1] {
+            MeleeClientOutput::Presence(request) => {
+                assert_eq!(request.req_type, DiscordClientRequestType::Game);
+                assert_eq!(request.mode, "Vs. Mode");
+                assert_eq!(request.matchup.as_ref().map(|m| m.to_string()), Some("Fox vs Marth".into()));
+            },
+            output => panic!("expected the presence of the game, got {:?}", output)
+        }
+
+        // only the first tick holds the reads in full, the others only what changed
+        let (_, first_tick) = record(vs_mode_ticks().into_iter().take(1).collect(), "first-tick");
+        assert!(trace.len() < first_tick.len() + 5 * 256, "{} bytes for 6 ticks, {} for the first one", trace.len(), first_tick.len());
+    }
+}
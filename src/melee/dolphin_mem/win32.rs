@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::mem;
+use std::str::from_utf8_unchecked;
+
+use windows::Win32::Foundation::ERROR_PARTIAL_COPY;
+use windows::Win32::Foundation::GetLastError;
+use windows::Win32::System::Diagnostics::Debug::ReadProcessMemory;
+use windows::Win32::System::Memory::MEMORY_BASIC_INFORMATION;
+use windows::Win32::System::Memory::VirtualQueryEx;
+use windows::Win32::System::ProcessStatus::PSAPI_WORKING_SET_EX_BLOCK;
+use windows::Win32::System::ProcessStatus::PSAPI_WORKING_SET_EX_INFORMATION;
+use windows::Win32::System::ProcessStatus::QueryWorkingSetEx;
+use windows::Win32::{System::{Diagnostics::ToolHelp::{CreateToolhelp32Snapshot, PROCESSENTRY32, TH32CS_SNAPPROCESS, Process32Next}, Threading::{OpenProcess, PROCESS_QUERY_INFORMATION, PROCESS_VM_READ, GetExitCodeProcess, QueryFullProcessImageNameA, PROCESS_NAME_WIN32}}, Foundation::{STILL_ACTIVE, HANDLE, CloseHandle}};
+use windows::core::PSTR;
+
+use super::{DolphinInstance, GC_RAM_SIZE, MemorySource, parse_game_id};
+
+const VALID_PROCESS_NAMES: &'static [&'static str] = &["Dolphin.exe", "Slippi Dolphin.exe", "DolphinWx.exe", "DolphinQt2.exe"];
+const MEM_MAPPED: u32 = 0x40000;
+
+pub struct DolphinMemory {
+    pid: Option<u32>,
+    process_handle: Option<HANDLE>,
+    dolphin_base_addr: Option<*mut c_void>,
+    dolphin_addr_size: Option<usize>,
+    // so switching between instances doesn't scan their memory again every time
+    known_regions: HashMap<u32, (*mut c_void, usize)>
+}
+
+impl DolphinMemory {
+    pub fn new() -> Self {
+        DolphinMemory { pid: None, process_handle: None, dolphin_base_addr: None, dolphin_addr_size: None, known_regions: HashMap::new() }
+    }
+
+    fn find_gamecube_ram_offset(&mut self) -> bool {
+        if !self.has_process() {
+            return false;
+        }
+
+        if let Some((base_addr, size)) = find_ram_region(self.process_handle.unwrap()) {
+            self.dolphin_base_addr = Some(base_addr);
+            self.dolphin_addr_size = Some(size);
+            self.known_regions.insert(self.pid.unwrap(), (base_addr, size));
+
+            println!("Dolphin Base Address: {:?}", self.dolphin_base_addr);
+            println!("Dolphin Address Size: {:?}", self.dolphin_addr_size);
+            return true;
+        }
+        return false;
+    }
+
+    fn has_gamecube_ram_offset(&self) -> bool {
+        self.dolphin_base_addr.is_some()
+    }
+
+    fn reset(&mut self) {
+        self.pid = None;
+        self.process_handle = None;
+        self.dolphin_base_addr = None;
+        self.dolphin_addr_size = None;
+    }
+}
+
+impl MemorySource for DolphinMemory {
+    fn find_process(&mut self) -> bool {
+        for (pid, name) in dolphin_processes() {
+            println!("{}", name);
+            if self.attach(pid) {
+                break;
+            }
+        }
+        return self.has_process();
+    }
+
+    fn has_process(&self) -> bool {
+        self.process_handle.is_some()
+    }
+
+    fn instances(&mut self) -> Vec<DolphinInstance> {
+        dolphin_processes().into_iter().filter_map(|(pid, name)| unsafe {
+            let handle = open_running_process(pid)?;
+            let exe = executable_path(handle).unwrap_or(name);
+            let game_id = find_ram_region(handle).and_then(|(base_addr, _)| {
+                let mut raw = [0u8; 6];
+                let mut memread: usize = 0;
+                let success = ReadProcessMemory(handle, base_addr as *const c_void, raw.as_mut_ptr() as *mut c_void, raw.len(), Some(&mut memread as *mut _));
+                if success.as_bool() && memread == raw.len() { parse_game_id(&raw) } else { None }
+            });
+            CloseHandle(handle);
+            Some(DolphinInstance { pid, exe, game_id })
+        }).collect()
+    }
+
+    fn attach(&mut self, pid: u32) -> bool {
+        if let Some(handle) = self.process_handle {
+            unsafe { CloseHandle(handle); }
+        }
+        self.reset();
+        self.process_handle = unsafe { open_running_process(pid) };
+        if self.has_process() {
+            self.pid = Some(pid);
+            if let Some(&(base_addr, size)) = self.known_regions.get(&pid) {
+                self.dolphin_base_addr = Some(base_addr);
+                self.dolphin_addr_size = Some(size);
+            }
+        }
+        self.has_process()
+    }
+
+    fn attached_pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    fn check_process_running(&mut self) -> bool {
+        if self.process_handle.is_none() {
+            return false;
+        }
+
+        let mut status: u32 = 0;
+        unsafe {
+            if GetExitCodeProcess(self.process_handle.unwrap(), &mut status as *mut _).as_bool() && status as i32 != STILL_ACTIVE.0 {
+                self.reset();
+                return false;
+            }
+        }
+        return true;
+    }
+
+    fn read_raw(&mut self, addr: u32, output: &mut [u8]) -> bool {
+        if !self.has_gamecube_ram_offset() && !self.find_gamecube_ram_offset() {
+            return false;
+        }
+
+        let raddr = self.dolphin_base_addr.unwrap() as usize + addr as usize;
+        let size = output.len();
+        let mut memread: usize = 0;
+
+        unsafe {
+            let success = ReadProcessMemory(self.process_handle.unwrap(), raddr as *const c_void, output.as_mut_ptr() as *mut c_void, size, Some(&mut memread as *mut _));
+            if success.as_bool() && memread == size {
+                return true;
+            } else {
+                let err = GetLastError().0;
+                println!("[MEMORY] Failed reading from address {:#08X} ERROR {}", addr, err);
+                if err == ERROR_PARTIAL_COPY.0 { // game probably closed, reset the dolphin ram offset
+                    self.dolphin_addr_size = None;
+                    self.dolphin_base_addr = None;
+                    self.known_regions.remove(&self.pid.unwrap());
+                }
+                return false;
+            }
+        }
+    }
+}
+
+fn dolphin_processes() -> Vec<(u32, String)> {
+    let mut processes = Vec::new();
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0).unwrap();
+        let mut pe32 = PROCESSENTRY32 {
+            dwSize: mem::size_of::<PROCESSENTRY32>() as u32,
+            cntUsage: 0,
+            th32ProcessID: 0,
+            th32DefaultHeapID: 0,
+            th32ModuleID: 0,
+            cntThreads: 0,
+            th32ParentProcessID: 0,
+            pcPriClassBase: 0,
+            dwFlags: 0,
+            szExeFile: [0; 260]
+        };
+
+        while Process32Next(snapshot, &mut pe32 as *mut _).as_bool() {
+            let name = from_utf8_unchecked(&pe32.szExeFile).trim_end_matches(char::from(0));
+            if VALID_PROCESS_NAMES.iter().any(|&e| name.starts_with(e)) {
+                processes.push((pe32.th32ProcessID, name.to_string()));
+            }
+        }
+        CloseHandle(snapshot);
+    }
+    processes
+}
+
+unsafe fn open_running_process(pid: u32) -> Option<HANDLE> {
+    let mut status: u32 = 0;
+    let handle = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, false, pid).ok()?;
+    if GetExitCodeProcess(handle, &mut status as *mut _).as_bool() && status as i32 == STILL_ACTIVE.0 {
+        return Some(handle);
+    }
+    CloseHandle(handle);
+    None
+}
+
+unsafe fn executable_path(handle: HANDLE) -> Option<String> {
+    let mut buf = [0u8; 260];
+    let mut size = buf.len() as u32;
+    if QueryFullProcessImageNameA(handle, PROCESS_NAME_WIN32, PSTR(buf.as_mut_ptr()), &mut size as *mut _).as_bool() {
+        return Some(String::from_utf8_lossy(&buf[..size as usize]).into_owned());
+    }
+    None
+}
+
+fn find_ram_region(handle: HANDLE) -> Option<(*mut c_void, usize)> {
+    unsafe {
+        let mut info: MEMORY_BASIC_INFORMATION = Default::default();
+        let mut address: usize = 0;
+
+        while VirtualQueryEx(handle, Some(address as *const c_void), &mut info as *mut _, mem::size_of::<MEMORY_BASIC_INFORMATION>()) == mem::size_of::<MEMORY_BASIC_INFORMATION>() {
+            address = address + info.RegionSize / mem::size_of::<usize>();
+            // Dolphin stores the GameCube RAM address space in 32MB chunks.
+            // Extended memory override can allow up to 64MB.
+            if info.RegionSize >= GC_RAM_SIZE && info.RegionSize % GC_RAM_SIZE == 0 && info.Type.0 == MEM_MAPPED {
+                let mut wsinfo = PSAPI_WORKING_SET_EX_INFORMATION {
+                    VirtualAddress: 0 as *mut c_void,
+                    VirtualAttributes: PSAPI_WORKING_SET_EX_BLOCK { Flags: 0 }
+                };
+                wsinfo.VirtualAddress = info.BaseAddress;
+
+                if QueryWorkingSetEx(handle, &mut wsinfo as *mut _ as *mut c_void, mem::size_of::<PSAPI_WORKING_SET_EX_INFORMATION>().try_into().unwrap()).as_bool() {
+                    if (wsinfo.VirtualAttributes.Flags & 1) == 1 && info.BaseAddress != 0 as *mut c_void {
+                        return Some((info.BaseAddress, info.RegionSize));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,332 @@
+use num_enum::TryFromPrimitive;
+
+use super::{results::GameOutcome, MatchmakingMode, MeleeScene, SlippiMenuScene};
+
+// pauses are told from a timer that should be running but doesn't move. It also stands still for about 2 seconds
+// during "Ready, GO!", so we wait well beyond that before calling it a pause
+const PAUSE_TICKS: u32 = 8;
+
+// reference: Slippi's game end event reads the end method from the match info
+#[derive(Debug, TryFromPrimitive, PartialEq, Clone, Copy)]
+#[repr(u8)]
+pub enum GameEndMethod {
+    Time = 1,
+    Game = 2,
+    NoContest = 7
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MeleeEvent {
+    EnteredCss(MeleeScene), // the mode whose character select screen we're on
+    QueueStarted(Option<SlippiMenuScene>),
+    OpponentFound(Option<SlippiMenuScene>),
+    GameStarted(MeleeScene),
+    GamePaused,
+    GameResumed,
+    GameEnded { scene: MeleeScene, result: Option<GameEndMethod> }, // None if the game scene was left before it was decided
+    GameResult { scene: MeleeScene, outcome: GameOutcome }, // follows GameEnded if we could tell who won
+    LeftMode(MeleeScene)
+}
+
+// What we read from memory in one tick
+pub struct MeleeObservation {
+    pub major: u8,
+    pub minor: u8,
+    pub scene: Option<MeleeScene>,
+    pub mode: Option<MeleeScene>, // any known scene of the current major scene
+    pub matchmaking: Option<MatchmakingMode>,
+    pub game_time: u32,
+    pub timer_running: bool,
+    pub end_method: u8
+}
+
+impl MeleeObservation {
+    fn in_game(&self) -> bool {
+        self.scene.map(|s| s.is_in_game()).unwrap_or(false)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum MeleeState {
+    Menus,
+    Css,
+    Queueing(Option<SlippiMenuScene>),
+    OpponentFound,
+    InGame { scene: MeleeScene, paused: bool },
+    GameOver(MeleeScene) // still in the game scene, but the game has been decided
+}
+
+pub struct SceneStateMachine {
+    state: MeleeState,
+    major: Option<u8>,
+    mode: Option<MeleeScene>,
+    end_method_cleared: bool, // whether the end method has been 0 since the game started, it may still hold the last game's
+    last_game_time: u32,
+    stalled_ticks: u32
+}
+
+impl SceneStateMachine {
+    pub fn new() -> Self {
+        SceneStateMachine { state: MeleeState::Menus, major: None, mode: None, end_method_cleared: false, last_game_time: 0, stalled_ticks: 0 }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        matches!(self.state, MeleeState::InGame { paused: true, .. })
+    }
+
+    // None if there's nothing to observe (no process or an unsupported game), which ends everything that's going on
+    pub fn update(&mut self, obs: Option<&MeleeObservation>) -> Vec<MeleeEvent> {
+        let mut events = Vec::new();
+
+        // the game scene was left before we saw the game being decided. The player blocks may already be reset by now,
+        // so there's no telling how it ended
+        if let MeleeState::InGame { scene, .. } = &self.state {
+            if !obs.map(|o| o.in_game()).unwrap_or(false) {
+                events.push(MeleeEvent::GameEnded { scene: *scene, result: None });
+                self.state = MeleeState::Menus;
+            }
+        }
+
+        // each major scene is one mode, its minor scenes are the css, the game itself, results etc.
+        if self.major != obs.map(|o| o.major) {
+            if let Some(mode) = self.mode.take() {
+                events.push(MeleeEvent::LeftMode(mode));
+            }
+            self.major = obs.map(|o| o.major);
+            self.state = MeleeState::Menus;
+        }
+        let obs = match obs {
+            Some(obs) => obs,
+            None => return events
+        };
+        if obs.mode.is_some() {
+            self.mode = obs.mode;
+        }
+
+        match self.state.clone() {
+            MeleeState::InGame { scene, paused } => {
+                // 0 until the game is decided
+                if obs.end_method == 0 {
+                    self.end_method_cleared = true;
+                }
+                if self.end_method_cleared && GameEndMethod::try_from(obs.end_method).is_ok() {
+                    events.push(MeleeEvent::GameEnded { scene, result: GameEndMethod::try_from(obs.end_method).ok() });
+                    self.state = MeleeState::GameOver(scene);
+                } else {
+                    if obs.timer_running && obs.game_time == self.last_game_time {
+                        self.stalled_ticks += 1;
+                    } else {
+                        self.stalled_ticks = 0;
+                    }
+                    let now_paused = self.stalled_ticks >= PAUSE_TICKS;
+                    if now_paused != paused {
+                        events.push(if now_paused { MeleeEvent::GamePaused } else { MeleeEvent::GameResumed });
+                        self.state = MeleeState::InGame { scene, paused: now_paused };
+                    }
+                }
+                self.last_game_time = obs.game_time;
+            },
+            // waiting for the game scene to be left. 1P modes go from one stage to the next without leaving it, the end
+            // method going back to 0 is all that tells the next game apart
+            MeleeState::GameOver(_) if obs.in_game() && obs.end_method != 0 => {},
+            _ if obs.in_game() => {
+                let scene = obs.scene.unwrap();
+                events.push(MeleeEvent::GameStarted(scene));
+                self.state = MeleeState::InGame { scene, paused: false };
+                self.end_method_cleared = obs.end_method == 0;
+                self.last_game_time = obs.game_time;
+                self.stalled_ticks = 0;
+            },
+            MeleeState::Css | MeleeState::Queueing(_) | MeleeState::OpponentFound if obs.minor == 0 => {
+                let slippi_scene = match obs.scene { Some(MeleeScene::SlippiCss(scene)) => scene, Some(MeleeScene::StageStriking) => Some(SlippiMenuScene::Ranked), _ => None };
+                match (&self.state, &obs.matchmaking) {
+                    (MeleeState::Css, Some(MatchmakingMode::Initializing | MatchmakingMode::Matchmaking)) => {
+                        events.push(MeleeEvent::QueueStarted(slippi_scene));
+                        self.state = MeleeState::Queueing(slippi_scene);
+                    },
+                    (MeleeState::Queueing(scene), Some(MatchmakingMode::OpponentConnecting | MatchmakingMode::ConnectionSuccess)) => {
+                        events.push(MeleeEvent::OpponentFound(*scene));
+                        self.state = MeleeState::OpponentFound;
+                    },
+                    (MeleeState::Queueing(_) | MeleeState::OpponentFound, Some(MatchmakingMode::Idle | MatchmakingMode::ErrorEncountered)) => {
+                        self.state = MeleeState::Css;
+                    },
+                    _ => {}
+                }
+            },
+            // each mode has its css as minor scene 0
+            _ if obs.minor == 0 && obs.mode.is_some() => {
+                events.push(MeleeEvent::EnteredCss(obs.mode.unwrap()));
+                self.state = MeleeState::Css;
+            },
+            _ => self.state = MeleeState::Menus
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::melee::{MatchmakingMode, MeleeScene, SlippiMenuScene};
+
+    use super::{GameEndMethod, MeleeEvent, MeleeObservation, SceneStateMachine, PAUSE_TICKS};
+
+    // Vs. Mode, minor scene 0 is the css and 2 the game
+    fn vs_mode(minor: u8, game_time: u32, end_method: u8) -> MeleeObservation {
+        MeleeObservation {
+            major: 2,
+            minor,
+            scene: Some(MeleeScene::VsMode).filter(|_| minor == 2),
+            mode: Some(MeleeScene::VsMode),
+            matchmaking: None,
+            game_time,
+            timer_running: true,
+            end_method
+        }
+    }
+
+    // Classic Mode, every minor scene but the css (0) counts as in game
+    fn classic(minor: u8, game_time: u32, end_method: u8) -> MeleeObservation {
+        MeleeObservation { scene: Some(MeleeScene::Classic).filter(|_| minor != 0), mode: Some(MeleeScene::Classic), major: 3, ..vs_mode(minor, game_time, end_method) }
+    }
+
+    fn menus() -> MeleeObservation {
+        MeleeObservation { major: 1, minor: 0, scene: None, mode: None, matchmaking: None, game_time: 0, timer_running: false, end_method: 0 }
+    }
+
+    // Slippi ranked, minor scene 0 is the css and 2 the game
+    fn ranked(minor: u8, matchmaking: MatchmakingMode, game_time: u32) -> MeleeObservation {
+        let scene = Some(SlippiMenuScene::Ranked);
+        MeleeObservation {
+            major: 8,
+            minor,
+            scene: Some(if minor == 2 { MeleeScene::SlippiOnline(scene) } else { MeleeScene::SlippiCss(scene) }),
+            mode: Some(MeleeScene::SlippiCss(scene)),
+            matchmaking: Some(matchmaking),
+            game_time,
+            timer_running: minor == 2,
+            end_method: 0
+        }
+    }
+
+    // feeds the observations one tick at a time and checks the events of each tick
+    fn check(table: Vec<(Option<MeleeObservation>, Vec<MeleeEvent>)>) {
+        let mut machine = SceneStateMachine::new();
+        for (tick, (obs, expected)) in table.into_iter().enumerate() {
+            assert_eq!(machine.update(obs.as_ref()), expected, "tick {}", tick);
+        }
+    }
+
+    fn game_ends(events: &[MeleeEvent]) -> Vec<&MeleeEvent> {
+        events.iter().filter(|e| matches!(e, MeleeEvent::GameEnded { .. })).collect()
+    }
+
+    #[test]
+    fn consecutive_games_with_the_same_end_method() {
+        let mut machine = SceneStateMachine::new();
+        let mut events = Vec::new();
+        // the end method of the first game is still set while the second one loads
+        for obs in [vs_mode(0, 0, 0), vs_mode(2, 0, 0), vs_mode(2, 1, 0), vs_mode(2, 2, 2), vs_mode(0, 2, 2),
+                    vs_mode(2, 0, 2), vs_mode(2, 1, 0), vs_mode(2, 2, 2), vs_mode(0, 2, 2)] {
+            events.extend(machine.update(Some(&obs)));
+        }
+        let ended = MeleeEvent::GameEnded { scene: MeleeScene::VsMode, result: Some(GameEndMethod::Game) };
+        assert_eq!(game_ends(&events), vec![&ended, &ended]);
+    }
+
+    #[test]
+    fn ready_go_is_no_pause() {
+        let mut machine = SceneStateMachine::new();
+        let mut events = machine.update(Some(&vs_mode(0, 0, 0)));
+        // the timer stands still during "Ready, GO!", then the game is paused for a while
+        let mut game_time = vec![0, 0, 0, 1, 2, 3];
+        game_time.extend([4; PAUSE_TICKS as usize + 2]);
+        game_time.extend([5, 6]);
+        for time in game_time {
+            events.extend(machine.update(Some(&vs_mode(2, time, 0))));
+        }
+        assert_eq!(events, vec![
+            MeleeEvent::EnteredCss(MeleeScene::VsMode),
+            MeleeEvent::GameStarted(MeleeScene::VsMode),
+            MeleeEvent::GamePaused,
+            MeleeEvent::GameResumed
+        ]);
+    }
+
+    #[test]
+    fn vs_mode_game() {
+        let vs = MeleeScene::VsMode;
+        let mut table = vec![
+            (Some(menus()), vec![]),
+            (Some(vs_mode(0, 0, 0)), vec![MeleeEvent::EnteredCss(vs)]),
+            (Some(vs_mode(1, 0, 0)), vec![]), // stage select
+            (Some(vs_mode(2, 0, 0)), vec![MeleeEvent::GameStarted(vs)]),
+            (Some(vs_mode(2, 1, 0)), vec![])
+        ];
+        // the timer stands still
+        table.extend((1..PAUSE_TICKS).map(|_| (Some(vs_mode(2, 1, 0)), vec![])));
+        table.extend([
+            (Some(vs_mode(2, 1, 0)), vec![MeleeEvent::GamePaused]),
+            (Some(vs_mode(2, 1, 0)), vec![]),
+            (Some(vs_mode(2, 2, 0)), vec![MeleeEvent::GameResumed]),
+            (Some(vs_mode(2, 3, 2)), vec![MeleeEvent::GameEnded { scene: vs, result: Some(GameEndMethod::Game) }]),
+            // "GAME!" and the results screen, the game is over but the scene still says in-game
+            (Some(vs_mode(2, 3, 2)), vec![]),
+            (Some(vs_mode(2, 3, 2)), vec![]),
+            (Some(vs_mode(4, 3, 2)), vec![]),
+            (Some(vs_mode(0, 3, 2)), vec![MeleeEvent::EnteredCss(vs)]),
+            (Some(menus()), vec![MeleeEvent::LeftMode(vs)])
+        ]);
+        check(table);
+    }
+
+    #[test]
+    fn game_scene_left_before_the_game_was_decided() {
+        let vs = MeleeScene::VsMode;
+        check(vec![
+            (Some(vs_mode(0, 0, 0)), vec![MeleeEvent::EnteredCss(vs)]),
+            (Some(vs_mode(2, 0, 0)), vec![MeleeEvent::GameStarted(vs)]),
+            (Some(vs_mode(2, 1, 0)), vec![]),
+            // quit out, the end method was set in between the ticks
+            (Some(vs_mode(0, 2, 7)), vec![MeleeEvent::GameEnded { scene: vs, result: None }, MeleeEvent::EnteredCss(vs)]),
+            (Some(vs_mode(2, 0, 7)), vec![MeleeEvent::GameStarted(vs)]),
+            // the process is gone
+            (None, vec![MeleeEvent::GameEnded { scene: vs, result: None }, MeleeEvent::LeftMode(vs)]),
+            (None, vec![])
+        ]);
+    }
+
+    #[test]
+    fn slippi_queue() {
+        let scene = Some(SlippiMenuScene::Ranked);
+        check(vec![
+            (Some(menus()), vec![]),
+            (Some(ranked(0, MatchmakingMode::Idle, 0)), vec![MeleeEvent::EnteredCss(MeleeScene::SlippiCss(scene))]),
+            (Some(ranked(0, MatchmakingMode::Initializing, 0)), vec![MeleeEvent::QueueStarted(scene)]),
+            (Some(ranked(0, MatchmakingMode::Matchmaking, 0)), vec![]),
+            // the queue was left and joined again
+            (Some(ranked(0, MatchmakingMode::Idle, 0)), vec![]),
+            (Some(ranked(0, MatchmakingMode::Matchmaking, 0)), vec![MeleeEvent::QueueStarted(scene)]),
+            (Some(ranked(0, MatchmakingMode::ConnectionSuccess, 0)), vec![MeleeEvent::OpponentFound(scene)]),
+            (Some(ranked(2, MatchmakingMode::ConnectionSuccess, 0)), vec![MeleeEvent::GameStarted(MeleeScene::SlippiOnline(scene))]),
+            (Some(ranked(2, MatchmakingMode::ConnectionSuccess, 1)), vec![])
+        ]);
+    }
+
+    #[test]
+    fn one_player_stages() {
+        let scene = MeleeScene::Classic;
+        let ended = MeleeEvent::GameEnded { scene, result: Some(GameEndMethod::Game) };
+        check(vec![
+            (Some(classic(0, 0, 0)), vec![MeleeEvent::EnteredCss(scene)]),
+            (Some(classic(1, 0, 0)), vec![MeleeEvent::GameStarted(scene)]),
+            (Some(classic(1, 1, 0)), vec![]),
+            (Some(classic(1, 2, 2)), vec![ended.clone()]),
+            // the splash screen of the next stage
+            (Some(classic(2, 2, 2)), vec![]),
+            (Some(classic(3, 0, 0)), vec![MeleeEvent::GameStarted(scene)]),
+            (Some(classic(3, 1, 2)), vec![ended]),
+            (Some(menus()), vec![MeleeEvent::LeftMode(scene)])
+        ]);
+    }
+}
@@ -0,0 +1,79 @@
+use super::{character::MeleeCharacter, game::{AddressProfile, MeleeGame, GAME_ID_ADDR}, player::PlayerKind, records::CHARACTER_RECORDS_LEN, stage::MeleeStage, R13_PLAYER_PORT};
+
+// Builds GameCube RAM images for tests, everything is placed where the bundled NTSC 1.02 profile looks for it
+pub struct TestRam {
+    mem: Vec<u8>,
+    profile: &'static AddressProfile
+}
+
+impl TestRam {
+    // len bytes from 0x80000000 on, with an NTSC 1.02 game running and nobody in any port
+    pub fn new(len: usize) -> Self {
+        let game = MeleeGame { id: "GALE01".into(), revision: 2 };
+        let mut ram = TestRam { mem: vec![0u8; len], profile: game.address_profile().unwrap() };
+        ram.write(GAME_ID_ADDR, b"GALE01\x00\x02");
+        for port in 0..4 {
+            ram.player_kind(port, PlayerKind::None);
+        }
+        ram
+    }
+
+    pub fn write(&mut self, addr: u32, data: &[u8]) -> &mut Self {
+        let start = (addr - GAME_ID_ADDR) as usize;
+        self.mem[start..start + data.len()].copy_from_slice(data);
+        self
+    }
+
+    pub fn scene(&mut self, major: u8, minor: u8) -> &mut Self {
+        let profile = self.profile;
+        self.write(profile.major_scene, &[major]).write(profile.minor_scene(), &[minor])
+    }
+
+    pub fn stage(&mut self, stage: MeleeStage) -> &mut Self {
+        self.write(self.profile.stage_info + 0x88 + 0x03, &[stage as u8])
+    }
+
+    pub fn local_port(&mut self, port: u8) -> &mut Self {
+        self.write(self.profile.r13(R13_PLAYER_PORT), &[port])
+    }
+
+    pub fn game_time(&mut self, seconds: u32) -> &mut Self {
+        self.write(self.profile.game_time, &seconds.to_be_bytes())
+    }
+
+    // offset into the match init block, see rules.rs
+    pub fn match_init(&mut self, offset: u32, data: &[u8]) -> &mut Self {
+        self.write(self.profile.match_init + offset, data)
+    }
+
+    // a human in the port, with the given character and stocks
+    pub fn player(&mut self, port: u8, character: MeleeCharacter, stocks: u8) -> &mut Self {
+        let block = self.profile.player_blocks[port as usize];
+        self.player_kind(port, PlayerKind::Human)
+            .write(block + 0x04, &(character as u32).to_be_bytes())
+            .write(block + 0x8E, &[stocks])
+    }
+
+    pub fn costume(&mut self, port: u8, costume: u8) -> &mut Self {
+        self.write(self.profile.player_blocks[port as usize] + 0x44, &[costume])
+    }
+
+    pub fn cpu(&mut self, port: u8, level: u8) -> &mut Self {
+        let block = self.profile.player_blocks[port as usize];
+        self.player_kind(port, PlayerKind::Cpu).write(block + 0x49, &[level])
+    }
+
+    // the 1P records of a character, see records.rs
+    pub fn character_records(&mut self, character: MeleeCharacter, data: &[u8]) -> &mut Self {
+        let records = self.profile.character_records.unwrap() + (character as usize * CHARACTER_RECORDS_LEN) as u32;
+        self.write(records, data)
+    }
+
+    fn player_kind(&mut self, port: u8, kind: PlayerKind) -> &mut Self {
+        self.write(self.profile.player_blocks[port as usize] + 0x08, &(kind as u32).to_be_bytes())
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        self.mem.clone()
+    }
+}
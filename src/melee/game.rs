@@ -0,0 +1,176 @@
+use std::{fmt::Display, fs};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Deserializer, de::Error};
+
+use crate::{config::APP_INFO, util::get_appdata_file};
+
+use super::{multiman::MultiManVariant, special::SpecialMeleeVariant};
+
+pub const GAME_ID_ADDR: u32 = 0x80000000;
+pub const GAME_ID_LEN: usize = 0x06;
+pub const GAME_REVISION_ADDR: u32 = 0x80000007;
+
+const PROFILES_VERSION: u32 = 1;
+// only NTSC 1.02 and the builds based on it are bundled. NTSC 1.00/1.01 (GALE01 rev 0/1) and PAL (GALP01) are detected,
+// but stay unsupported until someone adds their addresses to a profiles.json in the config directory
+const BUNDLED_PROFILES: &str = include_str!("../../assets/profiles.json");
+
+lazy_static! {
+    // profiles from the config directory come first, so they can override the bundled ones for the same game
+    static ref PROFILES: Vec<AddressProfile> = {
+        let mut profiles = load_user_profiles().unwrap_or_default();
+        profiles.extend(parse_profiles(BUNDLED_PROFILES).expect("bundled profiles are invalid"));
+        profiles
+    };
+}
+
+// 20XX and most m-ex builds keep the NTSC 1.02 game id and memory layout, so they're covered by its profile
+#[derive(Debug, PartialEq, Clone)]
+pub struct MeleeGame {
+    pub id: String,
+    pub revision: u8
+}
+
+impl MeleeGame {
+    pub fn address_profile(&self) -> Option<&'static AddressProfile> {
+        PROFILES.iter().find(|p| p.games.iter().any(|g| g.id == self.id && g.revision.map(|r| r == self.revision).unwrap_or(true)))
+    }
+}
+
+impl Display for MeleeGame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.address_profile() {
+            Some(profile) => write!(f, "{} ({} rev {})", profile.name, self.id, self.revision),
+            None => write!(f, "{} rev {}", self.id, self.revision)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ProfileFile {
+    version: u32,
+    profiles: Vec<AddressProfile>
+}
+
+#[derive(Deserialize)]
+pub struct GameMatch {
+    pub id: String,
+    pub revision: Option<u8> // any revision if not set
+}
+
+// Where things live in memory for one or more builds of the game, see assets/profiles.json
+#[derive(Deserialize)]
+pub struct AddressProfile {
+    pub name: String,
+    pub games: Vec<GameMatch>,
+    #[serde(deserialize_with = "address")]
+    pub major_scene: u32,
+    #[serde(deserialize_with = "address")]
+    pub stage_info: u32,
+    #[serde(deserialize_with = "address")]
+    pub game_time: u32,
+    #[serde(deserialize_with = "address")]
+    pub match_info: u32, // 0x08 = game end method
+    #[serde(deserialize_with = "address")]
+    pub match_init: u32, // reference: https://github.com/akaneia/m-ex/blob/master/MexTK/include/match.h#L136
+    // 0x04 = character, 0x05 = skin (reference: https://github.com/bkacjios/m-overlay/blob/master/source/modules/games/GALE01-2.lua#L199-L202)
+    #[serde(deserialize_with = "addresses")]
+    pub player_selection_blocks: [u32; 4],
+    #[serde(deserialize_with = "addresses")]
+    pub player_blocks: [u32; 4],
+    #[serde(deserialize_with = "address")]
+    pub r13: u32,
+    // 1P records of each character in the save data, not every profile knows where they are
+    #[serde(default, deserialize_with = "optional_address")]
+    pub character_records: Option<u32>,
+    #[serde(default, deserialize_with = "optional_address")]
+    pub frame_count: Option<u32>, // u32, frames since the scene started
+    pub scenes: Vec<SceneMapping>
+}
+
+impl AddressProfile {
+    pub fn minor_scene(&self) -> u32 { self.major_scene + 0x03 }
+    pub fn r13(&self, offset: u32) -> u32 { self.r13 - offset }
+    // the first mapping for the scene wins, so mappings for a specific game go before the general ones
+    pub fn scene(&self, game: &MeleeGame, major: u8, minor: u8) -> Option<SceneKind> {
+        self.scenes_of(game).find(|s| s.major == major && s.minor.map(|m| m == minor).unwrap_or(minor != 0)).map(|s| s.scene)
+    }
+    // the mode a major scene belongs to, its css mapping wins if there's one
+    pub fn mode(&self, game: &MeleeGame, major: u8) -> Option<SceneKind> {
+        self.scene(game, major, 0).or_else(|| self.scenes_of(game).find(|s| s.major == major).map(|s| s.scene))
+    }
+    fn scenes_of<'a>(&'a self, game: &'a MeleeGame) -> impl Iterator<Item = &'a SceneMapping> {
+        self.scenes.iter().filter(|s| s.game.as_ref().map(|id| *id == game.id).unwrap_or(true))
+    }
+
+    // used as long as no (supported) game has been detected
+    pub fn fallback() -> &'static AddressProfile {
+        PROFILES.last().unwrap()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SceneMapping {
+    pub major: u8,
+    pub minor: Option<u8>, // any minor scene but the css (0) if not set
+    pub game: Option<String>, // any game of the profile if not set
+    pub scene: SceneKind
+}
+
+// MeleeScene without the parts we have to read at runtime
+#[derive(Deserialize, Clone, Copy)]
+pub enum SceneKind {
+    VsMode,
+    UnclePunch,
+    TrainingMode,
+    SlippiOnline,
+    SlippiCss,
+    HomeRunContest,
+    TargetTest,
+    MultiManMelee(MultiManVariant),
+    Classic,
+    Adventure,
+    AllStar,
+    EventMatch,
+    SpecialMelee(SpecialMeleeVariant),
+    TournamentMelee
+}
+
+fn parse_profiles(data: &str) -> Result<Vec<AddressProfile>, String> {
+    let file = serde_json::from_str::<ProfileFile>(data).map_err(|e| e.to_string())?;
+    if file.version != PROFILES_VERSION {
+        return Err(format!("unsupported profile version {} (expected {})", file.version, PROFILES_VERSION));
+    }
+    Ok(file.profiles)
+}
+
+fn load_user_profiles() -> Option<Vec<AddressProfile>> {
+    let path = get_appdata_file(format!("{}/{}/profiles.json", APP_INFO.author, APP_INFO.name).as_str())?;
+    let data = fs::read_to_string(&path).ok()?;
+    match parse_profiles(&data) {
+        Ok(profiles) => Some(profiles),
+        Err(err) => {
+            println!("[PROFILES] Ignoring {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+// addresses are written as hex strings since json has no hex literals
+fn parse_address<E: Error>(value: &str) -> Result<u32, E> {
+    u32::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|_| E::custom(format!("invalid address {}", value)))
+}
+
+fn address<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    parse_address(&String::deserialize(deserializer)?)
+}
+
+fn optional_address<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    Option::<String>::deserialize(deserializer)?.map(|value| parse_address(&value)).transpose()
+}
+
+fn addresses<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u32; 4], D::Error> {
+    let values = <[String; 4]>::deserialize(deserializer)?;
+    Ok([parse_address(&values[0])?, parse_address(&values[1])?, parse_address(&values[2])?, parse_address(&values[3])?])
+}
@@ -0,0 +1,61 @@
+use crate::config::{AppConfig, DolphinSelection};
+
+use super::{dolphin_mem::{DolphinInstance, MemorySource}, MeleeClient};
+
+impl<M: MemorySource> MeleeClient<M> {
+    // attaches to the Dolphin instance the config asks for, returns all instances we found
+    pub(super) fn select_instance(&mut self, c: &AppConfig) -> Vec<DolphinInstance> {
+        let instances = self.mem.instances();
+        if instances.is_empty() {
+            return instances;
+        }
+
+        let previous = self.mem.attached_pid();
+        let pinned = c.dolphin.pinned_path.as_ref().and_then(|path| instances.iter().find(|i| &i.exe == path));
+        let target = pinned.map(|i| i.pid).or_else(|| match c.dolphin.selection {
+            DolphinSelection::PreferNetplay => instances.iter().filter(|i| i.game_id.is_some()).find(|i| !i.is_playback()).map(|i| i.pid),
+            DolphinSelection::PreferInGame => self.find_in_game(&instances)
+        }).or_else(|| {
+            // keep the current one if it's still around, otherwise anything that runs a game
+            instances.iter().find(|i| Some(i.pid) == previous).or(instances.iter().find(|i| i.game_id.is_some())).or(instances.first()).map(|i| i.pid)
+        });
+
+        if let Some(pid) = target {
+            if (self.mem.attached_pid() == Some(pid) || self.mem.attach(pid)) && previous != Some(pid) {
+                if let Some(instance) = instances.iter().find(|i| i.pid == pid) {
+                    println!("Reading from {} (PID {})", instance.name(), pid);
+                }
+            }
+        }
+        instances
+    }
+
+    fn find_in_game(&mut self, instances: &[DolphinInstance]) -> Option<u32> {
+        // look at the current instance first so we don't jump between two running games
+        let current = self.mem.attached_pid();
+        let mut candidates: Vec<u32> = instances.iter().filter(|i| i.game_id.is_some()).map(|i| i.pid).collect();
+        candidates.sort_by_key(|&pid| Some(pid) != current);
+
+        let in_game = candidates.into_iter().find(|&pid| {
+            if self.mem.attached_pid() != Some(pid) && !self.mem.attach(pid) {
+                return false;
+            }
+            // probe with the instance's own profile without touching the detected game of the client
+            match self.read_game().and_then(|game| game.address_profile().map(|profile| (game, profile))) {
+                Some((game, profile)) => {
+                    let previous = std::mem::replace(&mut self.profile, profile);
+                    let scene = self.scene_of(&game);
+                    self.profile = previous;
+                    scene.map(|s| s.is_in_game()).unwrap_or(false)
+                },
+                None => false
+            }
+        });
+        if let Some(pid) = current {
+            if in_game.is_none() && self.mem.attached_pid() != Some(pid) {
+                self.mem.attach(pid);
+            }
+        }
+        in_game
+    }
+}
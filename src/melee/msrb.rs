@@ -1,8 +1,7 @@
-use std::mem;
-
-use super::dolphin_mem::DolphinMemory;
+use super::dolphin_mem::{MemorySource, FromBigEndian, SnapshotMemory};
 
 const MATCH_STRUCT_LEN: isize = 0x138;
+const CSSDT_BUF_ADDR: u32 = 0x80005614; // reference: https://github.com/project-slippi/slippi-ssbm-asm/blob/0be644aff85986eae17e96f4c98b3342ab087d05/Online/Online.s#L31
 
 // reference: https://github.com/project-slippi/slippi-ssbm-asm/blob/0be644aff85986eae17e96f4c98b3342ab087d05/Online/Online.s#L311-L344
 #[derive(Clone, Copy)]
@@ -40,20 +39,38 @@ pub enum MSRBOffset {
     MsrbSize = Self::MsrbMatchId as isize + 51,
 }
 
-impl DolphinMemory {
+pub trait MSRBMemory: MemorySource {
     fn msrb_ptr(&mut self) -> Option<u32> {
-        const CSSDT_BUF_ADDR: u32 = 0x80005614; // reference: https://github.com/project-slippi/slippi-ssbm-asm/blob/0be644aff85986eae17e96f4c98b3342ab087d05/Online/Online.s#L31
         self.pointer_indirection(CSSDT_BUF_ADDR, 2)
     }
-    pub fn read_msrb<T: Sized>(&mut self, offset: MSRBOffset) -> Option<T> where [u8; mem::size_of::<T>()]: {
+    fn read_msrb<T: FromBigEndian>(&mut self, offset: MSRBOffset) -> Option<T> {
         self.msrb_ptr().and_then(|ptr| self.read::<T>(ptr + offset as u32))
     }
 
-    pub fn read_msrb_string<const LEN: usize>(&mut self, offset: MSRBOffset) -> Option<String> where [u8; mem::size_of::<[u8; LEN]>()]: {
+    fn read_msrb_string<const LEN: usize>(&mut self, offset: MSRBOffset) -> Option<String> {
         self.msrb_ptr().and_then(|ptr| self.read_string::<LEN>(ptr + offset as u32))
     }
 
-    pub fn read_msrb_string_shift_jis<const LEN: usize>(&mut self, offset: MSRBOffset) -> Option<String> where [u8; mem::size_of::<[u8; LEN]>()]: {
+    fn read_msrb_string_shift_jis<const LEN: usize>(&mut self, offset: MSRBOffset) -> Option<String> {
         self.msrb_ptr().and_then(|ptr| self.read_string_shift_jis::<LEN>(ptr + offset as u32))
     }
 }
+
+impl<M: MemorySource> MSRBMemory for M {}
+
+impl<M: MemorySource> SnapshotMemory<M> {
+    // captures the pointer chain as well as the whole buffer, so we don't walk the chain again for every msrb read
+    pub fn capture_msrb(&mut self) -> bool {
+        if !self.capture(CSSDT_BUF_ADDR, 4) {
+            return false;
+        }
+        let cssdt_ptr = match self.read::<u32>(CSSDT_BUF_ADDR) {
+            Some(ptr) if self.capture(ptr, 4) => ptr,
+            _ => return false
+        };
+        match self.read::<u32>(cssdt_ptr) {
+            Some(msrb_ptr) => self.capture(msrb_ptr, MSRBOffset::MsrbSize as usize),
+            None => false
+        }
+    }
+}
@@ -1,4 +1,6 @@
-#[derive(PartialEq, Clone, Copy)]
+use serde::Deserialize;
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize)]
 pub enum MultiManVariant {
     TenMan,
     HundredMan,
@@ -6,4 +8,19 @@ pub enum MultiManVariant {
     FifteenMinute,
     Endless,
     Cruel
-}
\ No newline at end of file
+}
+impl MultiManVariant {
+    // 10-Man and 100-Man are raced against the clock, the others are about KOs
+    pub fn ko_goal(&self) -> Option<u32> {
+        match *self {
+            Self::TenMan => Some(10),
+            Self::HundredMan => Some(100),
+            _ => None
+        }
+    }
+
+    // index into the Multi-Man Melee records of a character
+    pub fn record_index(&self) -> usize {
+        *self as usize
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt::Display;
+
+use num_enum::TryFromPrimitive;
+
+use super::{character::MeleeCharacter, dolphin_mem::big_endian_struct};
+
+// reference: https://github.com/bkacjios/m-overlay/blob/master/source/modules/games/GALE01-2.lua
+pub const PLAYER_BLOCK_LEN: usize = 0xE90;
+
+big_endian_struct! {
+    // start of the static player block
+    pub struct StaticPlayerBlock {
+        _state: u32,
+        pub character: u32,
+        pub kind: u32, // 0x08, see PlayerKind
+        _unknown_0c: [u8; 0x38],
+        pub costume: u8, // 0x44
+        _unknown_45: [u8; 0x02],
+        pub team: u8, // 0x47
+        _unknown_48: u8,
+        pub cpu_level: u8, // 0x49
+        _unknown_4a: [u8; 0x16],
+        pub percent: u16, // 0x60
+        _unknown_62: [u8; 0x2C],
+        pub stocks: u8 // 0x8E
+    }
+}
+
+#[derive(Debug, TryFromPrimitive, PartialEq, Clone, Copy)]
+#[repr(u8)]
+pub enum PlayerKind {
+    Human = 0,
+    Cpu = 1,
+    Demo = 2,
+    None = 3
+}
+
+// Everything we know about whoever is in one of the four ports
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlayerState {
+    pub port: u8, // 0-based
+    pub character: Option<MeleeCharacter>,
+    pub costume: u8,
+    pub team: u8, // only meaningful in team battles
+    pub kind: PlayerKind,
+    pub cpu_level: u8,
+    pub stocks: u8,
+    pub percent: u16
+}
+
+impl PlayerState {
+    pub fn from_block(port: u8, block: &StaticPlayerBlock) -> Option<Self> {
+        let kind = PlayerKind::try_from(block.kind as u8).ok().filter(|k| *k != PlayerKind::None)?;
+        Some(PlayerState {
+            port,
+            character: MeleeCharacter::try_from(block.character as u8).ok(),
+            costume: block.costume,
+            team: block.team,
+            kind,
+            cpu_level: block.cpu_level,
+            stocks: block.stocks,
+            percent: block.percent
+        })
+    }
+}
+
+// What a player is shown as, without anything that changes during the game
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PlayerCharacter {
+    pub character: Option<MeleeCharacter>,
+    pub cpu_level: Option<u8> // None for humans
+}
+
+impl PlayerCharacter {
+    pub fn of(player: &PlayerState) -> Self {
+        PlayerCharacter { character: player.character, cpu_level: Some(player.cpu_level).filter(|_| player.kind == PlayerKind::Cpu) }
+    }
+}
+
+impl Display for PlayerCharacter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let character = self.character.map(|c| c.to_string()).unwrap_or("?".into());
+        // Lv. 9 Marth (CPU)
+        match self.cpu_level {
+            Some(level) => write!(f, "Lv. {} {} (CPU)", level, character),
+            None => write!(f, "{}", character)
+        }
+    }
+}
+
+// Who plays against whom, the local player's side comes first
+#[derive(Debug, PartialEq, Clone)]
+pub struct Matchup {
+    pub sides: Vec<Vec<PlayerCharacter>>,
+    pub teams: bool
+}
+
+// groups the players by side, the local player's side comes first. None if there's nobody to play against
+pub(super) fn sides(players: &[PlayerState], local_port: Option<u8>, teams: bool) -> Option<Vec<Vec<&PlayerState>>> {
+    let mut sides: Vec<(u8, Vec<&PlayerState>)> = Vec::new();
+    for player in players {
+        // without teams, everyone is on their own
+        let side = if teams { player.team } else { player.port };
+        match sides.iter_mut().find(|(s, _)| *s == side) {
+            Some((_, members)) => members.push(player),
+            None => sides.push((side, vec![player]))
+        }
+    }
+    if sides.len() < 2 {
+        return None;
+    }
+    let local_side = players.iter().find(|p| Some(p.port) == local_port).map(|p| if teams { p.team } else { p.port });
+    sides.sort_by_key(|(side, _)| Some(*side) != local_side);
+    Some(sides.into_iter().map(|(_, members)| members).collect())
+}
+
+impl Matchup {
+    pub fn new(players: &[PlayerState], local_port: Option<u8>, teams: bool) -> Option<Self> {
+        let sides = sides(players, local_port, teams)?;
+        Some(Matchup { sides: sides.into_iter().map(|members| members.into_iter().map(PlayerCharacter::of).collect()).collect(), teams })
+    }
+}
+
+impl Display for Matchup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let side = |players: &Vec<PlayerCharacter>| players.iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>().join("/");
+        // 2v2 — Fox/Falco vs Marth/Sheik
+        if self.teams {
+            write!(f, "{} — ", self.sides.iter().map(|s| s.len().to_string()).collect::<Vec<String>>().join("v"))?;
+        }
+        write!(f, "{}", self.sides.iter().map(side).collect::<Vec<String>>().join(" vs "))
+    }
+}
+
+// Stocks left of each side, and percents where they fit (1v1)
+#[derive(Debug, PartialEq, Clone)]
+pub struct StockStatus {
+    pub stocks: Vec<u8>,
+    pub percents: Option<[u16; 2]>
+}
+
+impl StockStatus {
+    pub fn new(players: &[PlayerState], local_port: Option<u8>, teams: bool) -> Option<Self> {
+        let sides = sides(players, local_port, teams)?;
+        let percents = match (sides.len(), sides[0].as_slice(), sides[1].as_slice()) {
+            (2, [own], [other]) => Some([own.percent, other.percent]),
+            _ => None
+        };
+        Some(StockStatus { stocks: sides.iter().map(|members| members.iter().map(|p| p.stocks).sum()).collect(), percents })
+    }
+}
+
+impl Display for StockStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // 3 stocks (45%) vs 2 (120%)
+        for (i, stocks) in self.stocks.iter().enumerate() {
+            match i {
+                0 => write!(f, "{} stock{}", stocks, if *stocks == 1 { "" } else { "s" })?,
+                _ => write!(f, " vs {}", stocks)?
+            }
+            if let Some(percent) = self.percents.and_then(|p| p.get(i).copied()) {
+                write!(f, " ({}%)", percent)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt::Display;
+
+use crate::{config::AppConfig, discord::DiscordClientRequest};
+
+use super::{dolphin_mem::MemorySource, msrb::{MSRBMemory, MSRBOffset}, results::GameOutcome, stage::MeleeStage, MatchmakingMode, MeleeClient, MeleeEvent, MeleeScene, SlippiMenuScene};
+
+const MATCH_ID_LEN: usize = 51;
+const GAME_INFO_STAGE: u32 = 0x0E; // u16, external stage id
+
+// the score of the set before the current game is decided, e.g. "1 - 0, Game 2"
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RankedSetScore {
+    pub wins: u8,
+    pub losses: u8,
+    pub game: u8
+}
+
+impl Display for RankedSetScore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} - {}, Game {}", self.wins, self.losses, self.game)
+    }
+}
+
+// All games of a ranked set share the same match id
+pub struct RankedSet {
+    match_id: String,
+    score: RankedSetScore
+}
+
+impl<M: MemorySource> MeleeClient<M> {
+    pub(super) fn update_ranked_set(&mut self, event: &MeleeEvent) {
+        match event {
+            MeleeEvent::GameStarted(MeleeScene::SlippiOnline(Some(SlippiMenuScene::Ranked))) => {
+                // the game info block belongs to this game now, the next one is settled once the rng offset changes
+                self.striking_baseline = self.mem.read_msrb::<u32>(MSRBOffset::MsrbRngOffset);
+                let match_id = match self.get_match_id() {
+                    Some(match_id) => match_id,
+                    None => {
+                        self.ranked_set = None;
+                        return;
+                    }
+                };
+                match self.ranked_set.as_mut() {
+                    Some(set) if set.match_id == match_id => set.score.game += 1,
+                    _ => self.ranked_set = Some(RankedSet { match_id, score: RankedSetScore { wins: 0, losses: 0, game: 1 } })
+                }
+            },
+            MeleeEvent::GameResult { scene: MeleeScene::SlippiOnline(Some(SlippiMenuScene::Ranked)), outcome } => {
+                if let Some(set) = self.ranked_set.as_mut() {
+                    match outcome {
+                        GameOutcome::Won => set.score.wins += 1,
+                        GameOutcome::Lost => set.score.losses += 1,
+                        GameOutcome::Draw => {}
+                    }
+                }
+            },
+            MeleeEvent::LeftMode(_) => self.striking_baseline = None,
+            _ => {}
+        }
+    }
+
+    pub(super) fn ranked_score(&self) -> Option<RankedSetScore> {
+        self.ranked_set.as_ref().map(|set| set.score)
+    }
+
+    // both players locked in and are still connected, but the game hasn't loaded yet
+    pub(super) fn is_stage_striking(&mut self) -> bool {
+        matches!(self.matchmaking_type(), Some(MatchmakingMode::ConnectionSuccess)) &&
+            self.mem.read_msrb::<bool>(MSRBOffset::MsrbIsLocalPlayerReady).unwrap_or(false) &&
+            self.mem.read_msrb::<bool>(MSRBOffset::MsrbIsRemotePlayerReady).unwrap_or(false)
+    }
+
+    pub(super) fn stage_striking_request(&mut self, c: &AppConfig) -> DiscordClientRequest {
+        let opp_name = if c.slippi.show_opponent_name { self.get_opp_name() } else { None };
+        DiscordClientRequest::stage_striking(self.chosen_stage(), opp_name)
+    }
+
+    // The game info block keeps the stage of the last game until the strikes are done. Dolphin writes the picked stage together
+    // with a fresh rng offset for the next game, so we key on the rng offset instead of the stage, which may well be the same again.
+    // The strikes themselves aren't part of the matchmaking buffer.
+    fn chosen_stage(&mut self) -> Option<MeleeStage> {
+        let rng_offset = self.mem.read_msrb::<u32>(MSRBOffset::MsrbRngOffset)?;
+        // without a game before, whatever we see first is what's left from earlier
+        let baseline = *self.striking_baseline.get_or_insert(rng_offset);
+        if rng_offset == baseline || rng_offset == 0 {
+            return None;
+        }
+        self.mem.msrb_ptr().and_then(|ptr| self.mem.read::<u16>(ptr + MSRBOffset::MsrbGameInfoBlock as u32 + GAME_INFO_STAGE)).and_then(MeleeStage::from_external_id)
+    }
+
+    fn get_match_id(&mut self) -> Option<String> {
+        self.mem.read_msrb_string::<MATCH_ID_LEN>(MSRBOffset::MsrbMatchId).filter(|id| !id.is_empty())
+    }
+}
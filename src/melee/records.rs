@@ -0,0 +1,128 @@
+use std::fmt::Display;
+
+use super::{dolphin_mem::{big_endian_struct, MemorySource}, MeleeClient, MeleeEvent, MeleeScene};
+
+pub const CHARACTER_RECORDS_LEN: usize = 0x40;
+pub const CHARACTER_COUNT: usize = 0x1A; // indexed by external character id
+
+big_endian_struct! {
+    // the 1P records of one character in the save data, see character_records in assets/profiles.json
+    pub struct CharacterRecords {
+        pub target_test_frames: u32, // 0 if not cleared yet
+        pub home_run_distance: f32, // feet
+        pub multi_man: [u32; 6], // frames for 10-Man and 100-Man, KOs for the others (in MultiManVariant order)
+        _unknown_20: [u8; 0x20]
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PersonalRecord {
+    Distance(f32), // feet
+    Time(u32), // frames
+    Kos(u32)
+}
+
+impl Display for PersonalRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            // 1,204.3 ft
+            Self::Distance(feet) => {
+                let tenths = (feet * 10.0).round() as u64;
+                let whole = (tenths / 10).to_string();
+                let mut grouped = String::with_capacity(whole.len() + whole.len() / 3);
+                for (i, c) in whole.chars().enumerate() {
+                    if i > 0 && (whole.len() - i) % 3 == 0 {
+                        grouped.push(',');
+                    }
+                    grouped.push(c);
+                }
+                write!(f, "{}.{} ft", grouped, tenths % 10)
+            },
+            // 0:12.45
+            Self::Time(frames) => {
+                let centis = frames as u64 * 100 / 60;
+                write!(f, "{}:{:02}.{:02}", centis / 6000, centis / 100 % 60, centis % 100)
+            },
+            Self::Kos(kos) => write!(f, "{} KO{}", kos, if kos == 1 { "" } else { "s" })
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RecordStatus {
+    pub record: PersonalRecord,
+    pub is_new: bool // beaten during the current attempt
+}
+
+impl<M: MemorySource> MeleeClient<M> {
+    // the game only writes a record back once it has been beaten, so comparing with the one from the start of the attempt is enough
+    pub(super) fn update_record_baseline(&mut self, event: &MeleeEvent) {
+        match event {
+            MeleeEvent::GameStarted(scene @ (MeleeScene::HomeRunContest | MeleeScene::TargetTest(_) | MeleeScene::MultiManMelee(_))) => self.record_baseline = Some(self.personal_record(*scene)),
+            MeleeEvent::LeftMode(_) => self.record_baseline = None,
+            _ => {}
+        }
+    }
+
+    pub(super) fn record_status(&mut self, scene: MeleeScene) -> Option<RecordStatus> {
+        let record = self.personal_record(scene)?;
+        // if we attached during an attempt, we can't tell whether the record is from this one
+        let baseline = *self.record_baseline.get_or_insert(Some(record));
+        Some(RecordStatus { record, is_new: baseline != Some(record) })
+    }
+
+    // None if there's no record for the character yet
+    fn personal_record(&mut self, scene: MeleeScene) -> Option<PersonalRecord> {
+        let port = self.get_local_port(scene)?;
+        let character = self.get_character(port)? as usize;
+        if character >= CHARACTER_COUNT {
+            return None;
+        }
+        let records = self.mem.read::<CharacterRecords>(self.profile.character_records? + (character * CHARACTER_RECORDS_LEN) as u32)?;
+        match scene {
+            MeleeScene::HomeRunContest if records.home_run_distance > 0.0 => Some(PersonalRecord::Distance(records.home_run_distance)),
+            MeleeScene::TargetTest(_) if records.target_test_frames > 0 => Some(PersonalRecord::Time(records.target_test_frames)),
+            MeleeScene::MultiManMelee(variant) => match records.multi_man[variant.record_index()] {
+                0 => None,
+                record if variant.ko_goal().is_some() => Some(PersonalRecord::Time(record)),
+                record => Some(PersonalRecord::Kos(record))
+            },
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::melee::{character::MeleeCharacter, dolphin_mem::{FromBigEndian, MemoryDump}, fixtures::TestRam, MeleeClient, MeleeScene};
+
+    use super::{CharacterRecords, PersonalRecord, CHARACTER_RECORDS_LEN};
+
+    // 0:12.45 in Target Test, 1,204.3 ft in the Home-Run Contest and 23 KOs in Cruel Melee
+    fn records() -> Vec<u8> {
+        let mut raw = vec![0u8; CHARACTER_RECORDS_LEN];
+        raw[0x00..0x04].copy_from_slice(&747u32.to_be_bytes());
+        raw[0x04..0x08].copy_from_slice(&1204.3f32.to_be_bytes());
+        raw[0x1C..0x20].copy_from_slice(&23u32.to_be_bytes());
+        raw
+    }
+
+    #[test]
+    fn character_records_layout() {
+        assert_eq!(CharacterRecords::SIZE, CHARACTER_RECORDS_LEN);
+        let records = CharacterRecords::from_be_bytes(&records());
+        assert_eq!(PersonalRecord::Time(records.target_test_frames).to_string(), "0:12.45");
+        assert_eq!(PersonalRecord::Distance(records.home_run_distance).to_string(), "1,204.3 ft");
+        assert_eq!(records.multi_man, [0, 0, 0, 0, 0, 23]);
+    }
+
+    #[test]
+    fn record_of_the_local_port() {
+        let mut ram = TestRam::new(0x500000);
+        ram.scene(32, 1).local_port(1);
+        ram.player(1, MeleeCharacter::Fox, 1).character_records(MeleeCharacter::Fox, &records());
+        let mut client = MeleeClient::with_source(MemoryDump::from_bytes(ram.build()));
+        client.detect_game();
+        assert_eq!(client.personal_record(MeleeScene::HomeRunContest), Some(PersonalRecord::Distance(1204.3)));
+    }
+}
@@ -0,0 +1,146 @@
+use std::{cmp::Reverse, fmt::Display};
+
+use super::{dolphin_mem::MemorySource, events::GameEndMethod, player::{sides, PlayerState}, MeleeClient, MeleeEvent, MeleeScene};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GameOutcome {
+    Won,
+    Lost,
+    Draw
+}
+
+// The wins and losses of the local player in the current session, e.g. "7W - 3L"
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct WinLoss {
+    pub wins: u32,
+    pub losses: u32
+}
+
+impl WinLoss {
+    pub fn add(&mut self, outcome: GameOutcome) {
+        match outcome {
+            GameOutcome::Won => self.wins += 1,
+            GameOutcome::Lost => self.losses += 1,
+            GameOutcome::Draw => {}
+        }
+    }
+}
+
+impl Display for WinLoss {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}W - {}L", self.wins, self.losses)
+    }
+}
+
+impl<M: MemorySource> MeleeClient<M> {
+    // the scene state machine only sees how a game ended, who won has to be read while the player blocks are still there
+    pub(super) fn game_result(&mut self, event: &MeleeEvent) -> Option<MeleeEvent> {
+        let (scene, method) = match event {
+            MeleeEvent::GameEnded { scene: scene @ MeleeScene::SlippiOnline(_), result: Some(method) } => (*scene, *method),
+            MeleeEvent::GameEnded { scene, result: Some(method) } if scene.is_vs() => (*scene, *method),
+            _ => return None
+        };
+        let local_port = self.get_local_port(scene)?;
+        let players = self.get_players();
+        if !players.iter().any(|p| p.port == local_port) {
+            return None;
+        }
+        let teams = self.is_teams();
+        let sides = sides(&players, Some(local_port), teams)?;
+        let quitter = if method == GameEndMethod::NoContest { self.lras_initiator() } else { None };
+        let outcome = game_outcome(method, &sides, quitter)?;
+        Some(MeleeEvent::GameResult { scene, outcome })
+    }
+}
+
+// who won, from the sides of the game with the local player's side first. None if that can't be told (yet)
+fn game_outcome(method: GameEndMethod, sides: &[Vec<&PlayerState>], quitter: Option<u8>) -> Option<GameOutcome> {
+    match method {
+        GameEndMethod::Game => {
+            let standing: Vec<bool> = sides.iter().map(|side| side.iter().any(|p| p.stocks > 0)).collect();
+            match standing.iter().filter(|s| **s).count() {
+                0 => Some(GameOutcome::Draw),
+                1 => Some(if standing[0] { GameOutcome::Won } else { GameOutcome::Lost }),
+                _ => None
+            }
+        },
+        GameEndMethod::Time => Some(time_outcome(sides)),
+        // whoever quit out loses
+        GameEndMethod::NoContest => {
+            let quitter = quitter?;
+            if sides[0].iter().any(|p| p.port == quitter) {
+                Some(GameOutcome::Lost)
+            } else if sides[1..].iter().flatten().any(|p| p.port == quitter) {
+                Some(GameOutcome::Won)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+// more stocks left wins, then the lower damage
+fn time_outcome(sides: &[Vec<&PlayerState>]) -> GameOutcome {
+    let standing: Vec<(u32, Reverse<u32>)> = sides.iter()
+        .map(|side| (side.iter().map(|p| p.stocks as u32).sum(), Reverse(side.iter().map(|p| p.percent as u32).sum())))
+        .collect();
+    let best = *standing.iter().max().unwrap();
+    if standing[0] != best {
+        GameOutcome::Lost
+    } else if standing.iter().filter(|s| **s == best).count() > 1 {
+        GameOutcome::Draw
+    } else {
+        GameOutcome::Won
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::melee::{events::GameEndMethod, player::{sides, PlayerKind, PlayerState}};
+
+    use super::{game_outcome, GameOutcome};
+
+    fn player(port: u8, team: u8, stocks: u8, percent: u16) -> PlayerState {
+        PlayerState { port, character: None, costume: 0, team, kind: PlayerKind::Human, cpu_level: 0, stocks, percent }
+    }
+
+    // port 1 is the local player
+    fn outcome(method: GameEndMethod, players: &[PlayerState], teams: bool, quitter: Option<u8>) -> Option<GameOutcome> {
+        game_outcome(method, &sides(players, Some(0), teams).unwrap(), quitter)
+    }
+
+    #[test]
+    fn stocks() {
+        assert_eq!(outcome(GameEndMethod::Game, &[player(0, 0, 1, 80), player(1, 0, 0, 40)], false, None), Some(GameOutcome::Won));
+        assert_eq!(outcome(GameEndMethod::Game, &[player(0, 0, 0, 80), player(1, 0, 2, 40)], false, None), Some(GameOutcome::Lost));
+        // both lost their last stock on the same frame
+        assert_eq!(outcome(GameEndMethod::Game, &[player(0, 0, 0, 80), player(1, 0, 0, 40)], false, None), Some(GameOutcome::Draw));
+        // the blocks haven't caught up yet
+        assert_eq!(outcome(GameEndMethod::Game, &[player(0, 0, 1, 80), player(1, 0, 1, 40)], false, None), None);
+    }
+
+    #[test]
+    fn stocks_in_teams() {
+        let players = [player(0, 0, 0, 0), player(1, 1, 0, 0), player(2, 0, 2, 30), player(3, 1, 0, 0)];
+        assert_eq!(outcome(GameEndMethod::Game, &players, true, None), Some(GameOutcome::Won));
+        assert_eq!(outcome(GameEndMethod::Game, &players, false, None), Some(GameOutcome::Lost));
+    }
+
+    #[test]
+    fn time() {
+        assert_eq!(outcome(GameEndMethod::Time, &[player(0, 0, 2, 120), player(1, 0, 1, 10)], false, None), Some(GameOutcome::Won));
+        assert_eq!(outcome(GameEndMethod::Time, &[player(0, 0, 1, 50), player(1, 0, 1, 30)], false, None), Some(GameOutcome::Lost));
+        assert_eq!(outcome(GameEndMethod::Time, &[player(0, 0, 1, 30), player(1, 0, 1, 30)], false, None), Some(GameOutcome::Draw));
+        // a draw between the others doesn't make us win
+        assert_eq!(outcome(GameEndMethod::Time, &[player(0, 0, 1, 90), player(1, 0, 2, 30), player(2, 0, 2, 30)], false, None), Some(GameOutcome::Lost));
+    }
+
+    #[test]
+    fn no_contest() {
+        let players = [player(0, 0, 4, 0), player(1, 0, 4, 0)];
+        assert_eq!(outcome(GameEndMethod::NoContest, &players, false, Some(0)), Some(GameOutcome::Lost));
+        assert_eq!(outcome(GameEndMethod::NoContest, &players, false, Some(1)), Some(GameOutcome::Won));
+        assert_eq!(outcome(GameEndMethod::NoContest, &players, false, None), None);
+        assert_eq!(outcome(GameEndMethod::NoContest, &players, false, Some(3)), None);
+    }
+}
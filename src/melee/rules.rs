@@ -0,0 +1,140 @@
+use std::fmt::Display;
+
+use num_enum::TryFromPrimitive;
+
+use super::{dolphin_mem::{big_endian_struct, MemorySource}, player::PlayerKind, MeleeClient};
+
+// reference: https://github.com/project-slippi/slippi-wiki/blob/master/SPEC.md#game-start (the game info block is a copy of it)
+pub const MATCH_INIT_LEN: usize = 0xF0;
+
+big_endian_struct! {
+    struct MatchInitPlayer {
+        _character: u8,
+        kind: u8, // 0x01, see PlayerKind
+        stocks: u8, // 0x02
+        _costume: u8,
+        _unknown_04: [u8; 0x04],
+        handicap: u8, // 0x08
+        _unknown_09: [u8; 0x1B]
+    }
+}
+
+big_endian_struct! {
+    // the start of the match init block, see match_init in assets/profiles.json
+    struct MatchInit {
+        flags: u8, // 0x00, 0xE0 = game type, 0x03 = timer mode
+        flags_2: u8, // 0x01, 0x01 = friendly fire
+        _unknown_02: [u8; 0x06],
+        teams: bool, // 0x08
+        _unknown_09: [u8; 0x02],
+        item_frequency: i8, // 0x0B, -1 if items are off
+        _unknown_0c: [u8; 0x02],
+        _stage: u16, // 0x0E
+        time_limit: u32, // 0x10, seconds
+        _unknown_14: [u8; 0x4C],
+        players: [MatchInitPlayer; 4] // 0x60
+    }
+}
+
+// reference: https://github.com/akaneia/m-ex/blob/master/MexTK/include/match.h#L11-L14
+#[derive(Debug, TryFromPrimitive, PartialEq, Clone, Copy)]
+#[repr(u8)]
+pub enum TimerMode {
+    Countup = 3,
+    Countdown = 2,
+    Hidden = 1,
+    Frozen = 0,
+}
+
+#[derive(Debug, TryFromPrimitive, PartialEq, Clone, Copy)]
+#[repr(u8)]
+pub enum GameType {
+    Time = 0,
+    Stock = 1,
+    Coin = 2,
+    Bonus = 3
+}
+
+#[derive(Debug, TryFromPrimitive, PartialEq, Clone, Copy)]
+#[repr(i8)]
+pub enum ItemFrequency {
+    Off = -1,
+    VeryLow = 0,
+    Low = 1,
+    Medium = 2,
+    High = 3,
+    VeryHigh = 4
+}
+
+impl Display for ItemFrequency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Off => write!(f, "off"),
+            Self::VeryLow => write!(f, "very low"),
+            Self::Low => write!(f, "low"),
+            Self::Medium => write!(f, "medium"),
+            Self::High => write!(f, "high"),
+            Self::VeryHigh => write!(f, "very high")
+        }
+    }
+}
+
+// The rules the current match was started with
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MatchRules {
+    pub game_type: Option<GameType>,
+    pub timer: Option<TimerMode>,
+    pub stocks: Option<u8>, // of the first player, stock matches only
+    pub time_limit: Option<u32>, // seconds, only if the timer counts down
+    pub items: Option<ItemFrequency>,
+    pub teams: bool,
+    pub friendly_fire: bool,
+    pub handicaps: [Option<u8>; 4] // by port, None if nobody plays on it
+}
+
+impl Display for MatchRules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // 4-stock, 8:00, items off, friendly fire, handicap 9/5
+        let mut parts = Vec::with_capacity(5);
+        match (self.game_type, self.stocks) {
+            (Some(GameType::Stock), Some(stocks)) => parts.push(format!("{}-stock", stocks)),
+            (Some(GameType::Time), _) => parts.push("Time".into()),
+            (Some(GameType::Coin), _) => parts.push("Coin".into()),
+            (Some(GameType::Bonus), _) => parts.push("Bonus".into()),
+            _ => {}
+        }
+        if let Some(time_limit) = self.time_limit {
+            parts.push(format!("{}:{:02}", time_limit / 60, time_limit % 60));
+        }
+        if let Some(items) = self.items {
+            parts.push(format!("items {}", items));
+        }
+        if self.teams && self.friendly_fire {
+            parts.push("friendly fire".into());
+        }
+        // only worth mentioning if the players don't all have the same one
+        let handicaps: Vec<u8> = self.handicaps.iter().flatten().copied().collect();
+        if handicaps.iter().any(|h| *h != handicaps[0]) {
+            parts.push(format!("handicap {}", handicaps.iter().map(|h| h.to_string()).collect::<Vec<String>>().join("/")));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl<M: MemorySource> MeleeClient<M> {
+    pub(super) fn match_rules(&mut self) -> Option<MatchRules> {
+        let init = self.mem.read::<MatchInit>(self.profile.match_init)?;
+        let game_type = GameType::try_from(init.flags >> 5).ok();
+        let timer = TimerMode::try_from(init.flags & 0x03).ok();
+        Some(MatchRules {
+            game_type,
+            timer,
+            stocks: init.players.iter().find(|p| p.kind != PlayerKind::None as u8).map(|p| p.stocks).filter(|_| game_type == Some(GameType::Stock)),
+            time_limit: Some(init.time_limit).filter(|_| timer == Some(TimerMode::Countdown)),
+            items: ItemFrequency::try_from(init.item_frequency).ok(),
+            teams: init.teams,
+            friendly_fire: init.flags_2 & 0x01 != 0,
+            handicaps: std::array::from_fn(|i| Some(init.players[i].handicap).filter(|_| init.players[i].kind != PlayerKind::None as u8))
+        })
+    }
+}
@@ -0,0 +1,55 @@
+use super::{results::WinLoss, MeleeEvent, MeleeScene};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MeleeSession {
+    pub mode: MeleeScene,
+    pub start: i64,
+    pub games: u32,
+    pub record: WinLoss // only games we could tell the winner of
+}
+
+// A session lasts as long as we stay in the same major scene, across character select screens and games
+pub struct SessionTracker {
+    session: Option<MeleeSession>
+}
+
+impl SessionTracker {
+    pub fn new() -> Self {
+        SessionTracker { session: None }
+    }
+
+    pub fn session(&self) -> Option<&MeleeSession> {
+        self.session.as_ref()
+    }
+
+    pub fn handle(&mut self, event: &MeleeEvent, now: i64) {
+        match event {
+            MeleeEvent::EnteredCss(scene) | MeleeEvent::GameStarted(scene) => {
+                if self.session.is_none() {
+                    self.session = Some(MeleeSession { mode: session_mode(*scene), start: now, games: 0, record: WinLoss::default() });
+                }
+            },
+            MeleeEvent::GameEnded { .. } => {
+                if let Some(session) = self.session.as_mut() {
+                    session.games += 1;
+                }
+            },
+            MeleeEvent::GameResult { outcome, .. } => {
+                if let Some(session) = self.session.as_mut() {
+                    session.record.add(*outcome);
+                }
+            },
+            MeleeEvent::LeftMode(_) => self.session = None,
+            _ => {}
+        }
+    }
+}
+
+// the mode itself, without what's specific to the screen or game we're in
+fn session_mode(scene: MeleeScene) -> MeleeScene {
+    match scene {
+        MeleeScene::SlippiCss(scene) => MeleeScene::SlippiOnline(scene),
+        MeleeScene::TargetTest(_) => MeleeScene::TargetTest(None),
+        scene => scene
+    }
+}
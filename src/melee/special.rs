@@ -0,0 +1,33 @@
+use std::fmt::Display;
+
+use serde::Deserialize;
+
+// each of them has its own major scene
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize)]
+pub enum SpecialMeleeVariant {
+    Stamina,
+    SuperSuddenDeath,
+    Giant,
+    Tiny,
+    Invisible,
+    FixedCamera,
+    SingleButton,
+    Lightning,
+    SloMo
+}
+
+impl Display for SpecialMeleeVariant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Stamina => write!(f, "Stamina Mode"),
+            Self::SuperSuddenDeath => write!(f, "Super Sudden Death"),
+            Self::Giant => write!(f, "Giant Melee"),
+            Self::Tiny => write!(f, "Tiny Melee"),
+            Self::Invisible => write!(f, "Invisible Melee"),
+            Self::FixedCamera => write!(f, "Fixed-Camera Mode"),
+            Self::SingleButton => write!(f, "Single-Button Mode"),
+            Self::Lightning => write!(f, "Lightning Melee"),
+            Self::SloMo => write!(f, "Slo-Mo Melee")
+        }
+    }
+}
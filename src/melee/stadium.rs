@@ -0,0 +1,69 @@
+use std::fmt::Display;
+
+use super::{dolphin_mem::MemorySource, player::StaticPlayerBlock, records::PersonalRecord, MeleeClient, MeleeEvent, MeleeScene};
+
+// the sandbag always takes the second port
+const SANDBAG_PORT: usize = 1;
+
+// How far the current Stadium attempt is
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StadiumProgress {
+    HomeRun { damage: u16 }, // of the sandbag
+    TargetTest { frames: u32 },
+    MultiMan { frames: u32, finished: bool } // time of the run
+}
+
+impl Display for StadiumProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::HomeRun { damage } => write!(f, "Sandbag at {}%", damage),
+            Self::TargetTest { frames } => write!(f, "{}", PersonalRecord::Time(frames)),
+            Self::MultiMan { frames, finished: true } => write!(f, "Result: {}", PersonalRecord::Time(frames)),
+            Self::MultiMan { frames, finished: false } => write!(f, "{}", PersonalRecord::Time(frames))
+        }
+    }
+}
+
+impl<M: MemorySource> MeleeClient<M> {
+    // the time of an attempt stops once it's over, so the result stays until we leave the game
+    pub(super) fn update_attempt(&mut self, event: &MeleeEvent) {
+        match event {
+            MeleeEvent::GameStarted(MeleeScene::TargetTest(_) | MeleeScene::MultiManMelee(_)) => {
+                self.attempt_frames = (self.frame_count(), None);
+            },
+            MeleeEvent::GameEnded { scene: scene @ (MeleeScene::TargetTest(_) | MeleeScene::MultiManMelee(_)), .. } => {
+                self.attempt_frames.1 = self.frame_count();
+                if let Some(result @ StadiumProgress::MultiMan { .. }) = self.stadium_progress(*scene) {
+                    println!("[STADIUM] {} {}", scene, result);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    pub(super) fn stadium_progress(&mut self, scene: MeleeScene) -> Option<StadiumProgress> {
+        match scene {
+            MeleeScene::HomeRunContest => {
+                let sandbag = self.mem.read::<StaticPlayerBlock>(self.profile.player_blocks[SANDBAG_PORT])?;
+                Some(StadiumProgress::HomeRun { damage: sandbag.percent })
+            },
+            MeleeScene::TargetTest(_) => Some(StadiumProgress::TargetTest { frames: self.attempt_elapsed()? }),
+            MeleeScene::MultiManMelee(_) => Some(StadiumProgress::MultiMan { frames: self.attempt_elapsed()?, finished: self.attempt_frames.1.is_some() }),
+            _ => None
+        }
+    }
+
+    fn attempt_elapsed(&mut self) -> Option<u32> {
+        let end = match self.attempt_frames.1 {
+            Some(end) => end,
+            None => self.frame_count()?
+        };
+        // if we attached during an attempt, count from there
+        let start = *self.attempt_frames.0.get_or_insert(end);
+        Some(end.saturating_sub(start))
+    }
+
+    fn frame_count(&mut self) -> Option<u32> {
+        self.profile.frame_count.and_then(|addr| self.mem.read::<u32>(addr))
+    }
+}
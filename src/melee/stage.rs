@@ -80,6 +80,43 @@ impl MeleeStage {
     fn is_target_test(&self) -> bool {
         *self as u8 >= MeleeStage::MarioTargetTest as u8 && *self as u8 <= MeleeStage::GanondorfTargetTest as u8
     }
+
+    // the external stage id, as used by the match struct and Slippi replays (reference: https://github.com/project-slippi/slippi-wiki/blob/master/SPEC.md#melee-ids)
+    pub fn from_external_id(id: u16) -> Option<Self> {
+        Some(match id {
+            2 => Self::Izumi,
+            3 => Self::PStad,
+            4 => Self::Castle,
+            5 => Self::Kongo,
+            6 => Self::Zebes,
+            7 => Self::Corneria,
+            8 => Self::Story,
+            9 => Self::Onett,
+            10 => Self::MuteCity,
+            11 => Self::Rcruise,
+            12 => Self::Garden,
+            13 => Self::Greatbay,
+            14 => Self::Shrine,
+            15 => Self::Kraid,
+            16 => Self::Yoster,
+            17 => Self::Greens,
+            18 => Self::Fourside,
+            19 => Self::Mk1,
+            20 => Self::Mk2,
+            21 => Self::Akaneia,
+            22 => Self::Venom,
+            23 => Self::Pura,
+            24 => Self::BigBlue,
+            25 => Self::IceMt,
+            27 => Self::FlatZone,
+            28 => Self::OldPu,
+            29 => Self::OldStory,
+            30 => Self::OldKongo,
+            31 => Self::Battle,
+            32 => Self::FD,
+            _ => return None
+        })
+    }
 }
 
 impl Display for MeleeStage {
@@ -0,0 +1,50 @@
+use std::fmt::Display;
+
+use super::{dolphin_mem::MemorySource, msrb::{MSRBMemory, MSRBOffset}, MeleeClient};
+
+const PLAYER_NAME_OFFSETS: [MSRBOffset; 4] = [MSRBOffset::MsrbP1Name, MSRBOffset::MsrbP2Name, MSRBOffset::MsrbP3Name, MSRBOffset::MsrbP4Name];
+
+// The other players of a Slippi Teams game, by name
+#[derive(Debug, PartialEq, Clone)]
+pub struct TeamNames {
+    pub teammates: Vec<String>,
+    pub opponents: Vec<String>
+}
+
+impl Display for TeamNames {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // With X vs Y & Z
+        if !self.teammates.is_empty() {
+            write!(f, "With {} ", self.teammates.join(" & "))?;
+        }
+        write!(f, "vs {}", self.opponents.join(" & "))
+    }
+}
+
+impl<M: MemorySource> MeleeClient<M> {
+    pub(super) fn get_team_names(&mut self) -> Option<TeamNames> {
+        let local = self.mem.read_msrb::<u8>(MSRBOffset::MsrbLocalPlayerIndex)?;
+        let left = self.get_side(MSRBOffset::MsrbVsLeftPlayers)?;
+        let right = self.get_side(MSRBOffset::MsrbVsRightPlayers)?;
+        let (own, other) = if left.contains(&local) { (left, right) } else if right.contains(&local) { (right, left) } else { return None };
+
+        let teammates = own.into_iter().filter(|port| *port != local).filter_map(|port| self.get_player_name(port)).collect();
+        let opponents: Vec<String> = other.into_iter().filter_map(|port| self.get_player_name(port)).collect();
+        if opponents.is_empty() {
+            return None;
+        }
+        Some(TeamNames { teammates, opponents })
+    }
+
+    // the ports of one side of the vs screen, packed one per byte
+    fn get_side(&mut self, offset: MSRBOffset) -> Option<Vec<u8>> {
+        self.mem.read_msrb::<[u8; 4]>(offset).map(|ports| ports.into_iter().filter(|port| *port < 4).collect())
+    }
+
+    // falls back to the connect code if the player has no display name
+    fn get_player_name(&mut self, port: u8) -> Option<String> {
+        self.mem.read_msrb_string::<31>(PLAYER_NAME_OFFSETS[port as usize])
+            .filter(|name| !name.is_empty())
+            .or_else(|| self.get_player_connect_code(port).filter(|code| !code.is_empty()))
+    }
+}
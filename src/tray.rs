@@ -1,9 +1,12 @@
-use std::{mem::MaybeUninit, sync::{atomic::{AtomicBool, self}, Arc, mpsc::Receiver}};
+use std::{sync::{atomic::{AtomicBool, self}, Arc, Mutex, mpsc::Receiver}};
+#[cfg(windows)]
+use std::mem::MaybeUninit;
 
 use trayicon::{TrayIconBuilder, MenuBuilder};
+#[cfg(windows)]
 use windows::Win32::UI::WindowsAndMessaging::{TranslateMessage, DispatchMessageA, PeekMessageA, PM_REMOVE};
 
-use crate::{config::{CONFIG, AppConfig, write_config, APP_INFO}, util::get_appdata_file};
+use crate::{config::{CONFIG, AppConfig, DolphinSelection, write_config, APP_INFO}, melee::DolphinInstance, util::get_appdata_file};
 
 use {std::sync::mpsc};
 
@@ -35,10 +38,18 @@ impl From<ExtendedMenuBuilder> for MenuBuilder<TrayEvents> {
     }
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Clone)]
 pub enum MeleeTrayEvent {
     Connected,
-    Disconnected
+    Disconnected,
+    UnsupportedGame,
+    Instances(Vec<DolphinInstance>, Option<u32>) // attached pid
+}
+
+struct MeleeTrayState {
+    status: MeleeTrayEvent,
+    instances: Vec<DolphinInstance>,
+    attached_pid: Option<u32>
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -48,6 +59,14 @@ enum TrayEvents {
     // Global
     ShowInGameCharacter,
     ShowInGameTime,
+    ShowSession,
+    ShowStocks,
+    ShowWinLoss,
+
+    // Dolphin
+    DolphinPreferNetplay,
+    DolphinPreferInGame,
+    DolphinPin(String),
 
     // Slippi
     EnableSlippi,
@@ -58,6 +77,7 @@ enum TrayEvents {
     SlippiRankedShowRank,
     SlippiRankedShowViewRankedProfileButton,
     SlippiRankedShowScore,
+    SlippiRankedShowStageStriking,
 
     SlippiEnableUnranked,
 
@@ -70,31 +90,55 @@ enum TrayEvents {
 
     // Training Mode
     EnableTrainingMode,
+    TrainingModeShowPartner,
 
     // Vs. Mode
     EnableVsMode,
+    VsModeShowRules,
 
     // Stadium
     EnableStadium,
 
     StadiumEnableHRC,
+    StadiumHRCShowRecord,
+    StadiumHRCShowProgress,
     
     StadiumEnableBTT,
     StadiumBTTShowStageName,
+    StadiumBTTShowRecord,
+    StadiumBTTShowProgress,
 
     StadiumEnableMMM,
+    StadiumMMMShowRecord,
+    StadiumMMMShowProgress,
+
+    // 1P Mode
+    EnableOnePlayer,
+
+    OnePlayerEnableClassic,
+
+    OnePlayerEnableAdventure,
+
+    OnePlayerEnableAllStar,
+
+    OnePlayerEnableEventMatch,
 
     // Miscallaneous
     OpenConfig,
     Quit,
 }
 
-fn build_menu(melee_connected: &Arc<AtomicBool>) -> MenuBuilder<TrayEvents> {
+fn build_menu(melee_state: &Arc<Mutex<MeleeTrayState>>) -> MenuBuilder<TrayEvents> {
+    let melee_state = melee_state.lock().unwrap();
     CONFIG.with_ref(|c| {
         MenuBuilder::new()
         .with(trayicon::MenuItem::Item {
             id: TrayEvents::_Unused,
-            name: if melee_connected.load(atomic::Ordering::Relaxed) { "✔️ Connected to dolphin process" } else { "❌ Searching for dolphin process..." }.into(),
+            name: match melee_state.status {
+                MeleeTrayEvent::Connected => "✔️ Connected to dolphin process",
+                MeleeTrayEvent::UnsupportedGame => "⚠️ Unsupported game version running",
+                _ => "❌ Searching for dolphin process..."
+            }.into(),
             disabled: true,
             icon: None
         })
@@ -104,6 +148,29 @@ fn build_menu(melee_connected: &Arc<AtomicBool>) -> MenuBuilder<TrayEvents> {
                 MenuBuilder::new()
                     .checkable("Show Character", c.global.show_in_game_character, TrayEvents::ShowInGameCharacter)
                     .checkable("Show In-Game Time", c.global.show_in_game_time, TrayEvents::ShowInGameTime)
+                    .checkable("Show overall game session when not in-game", c.global.show_session, TrayEvents::ShowSession)
+                    .checkable("Show Stocks and Percents", c.global.show_stocks, TrayEvents::ShowStocks)
+                    .checkable("Show Session Wins and Losses", c.global.show_win_loss, TrayEvents::ShowWinLoss)
+        )
+        .submenu(
+            "Dolphin",
+            melee_state.instances.iter().fold(
+                MenuBuilder::new()
+                    .checkable("Prefer netplay Dolphin", c.dolphin.selection == DolphinSelection::PreferNetplay, TrayEvents::DolphinPreferNetplay)
+                    .checkable("Prefer Dolphin that is in-game", c.dolphin.selection == DolphinSelection::PreferInGame, TrayEvents::DolphinPreferInGame)
+                    .separator(),
+                // checking an instance pins its executable, unchecking goes back to the preference above
+                |menu, instance| menu.checkable(
+                    format!("{}{} (PID {}) - {}",
+                        if melee_state.attached_pid == Some(instance.pid) { "▶ " } else { "" },
+                        instance.name(),
+                        instance.pid,
+                        instance.game_id.as_deref().unwrap_or("No game running")
+                    ).as_str(),
+                    c.dolphin.pinned_path.as_ref() == Some(&instance.exe),
+                    TrayEvents::DolphinPin(instance.exe.clone())
+                )
+            )
         )
         .submenu(
             "Slippi Online",
@@ -118,6 +185,7 @@ fn build_menu(melee_connected: &Arc<AtomicBool>) -> MenuBuilder<TrayEvents> {
                             .cwec("Show rank", c.slippi.ranked.show_rank, TrayEvents::SlippiRankedShowRank, &[c.slippi.enabled, c.slippi.ranked.enabled])
                             .cwec("Show \"View Ranked Profile\" button", c.slippi.ranked.show_view_ranked_profile_button, TrayEvents::SlippiRankedShowViewRankedProfileButton, &[c.slippi.enabled, c.slippi.ranked.enabled])
                             .cwec("Show match score", c.slippi.ranked.show_score, TrayEvents::SlippiRankedShowScore, &[c.slippi.enabled, c.slippi.ranked.enabled])
+                            .cwec("Show stage striking", c.slippi.ranked.show_stage_striking, TrayEvents::SlippiRankedShowStageStriking, &[c.slippi.enabled, c.slippi.ranked.enabled])
                             .into()
                     )
                     .submenu(
@@ -147,13 +215,17 @@ fn build_menu(melee_connected: &Arc<AtomicBool>) -> MenuBuilder<TrayEvents> {
         )
         .submenu(
             "Training Mode",
-            MenuBuilder::new()
+            ExtendedMenuBuilder::new()
                     .checkable("Enabled", c.training_mode.enabled, TrayEvents::EnableTrainingMode)
+                    .cwec("Show training partner", c.training_mode.show_partner, TrayEvents::TrainingModeShowPartner, &[c.training_mode.enabled])
+                    .into()
         )
         .submenu(
             "Vs. Mode",
-            MenuBuilder::new()
+            ExtendedMenuBuilder::new()
                     .checkable("Enabled", c.vs_mode.enabled, TrayEvents::EnableVsMode)
+                    .cwec("Show match rules", c.vs_mode.show_rules, TrayEvents::VsModeShowRules, &[c.vs_mode.enabled])
+                    .into()
         )
         .submenu(
             "Stadium",
@@ -163,6 +235,8 @@ fn build_menu(melee_connected: &Arc<AtomicBool>) -> MenuBuilder<TrayEvents> {
                         "Home-Run Contest",
                     ExtendedMenuBuilder::new()
                             .cwec("Enabled", c.stadium.hrc.enabled, TrayEvents::StadiumEnableHRC, &[c.stadium.enabled])
+                            .cwec("Show personal record", c.stadium.hrc.show_record, TrayEvents::StadiumHRCShowRecord, &[c.stadium.enabled, c.stadium.hrc.enabled])
+                            .cwec("Show sandbag damage", c.stadium.hrc.show_progress, TrayEvents::StadiumHRCShowProgress, &[c.stadium.enabled, c.stadium.hrc.enabled])
                             .into()
                     )
                     .submenu(
@@ -170,12 +244,46 @@ fn build_menu(melee_connected: &Arc<AtomicBool>) -> MenuBuilder<TrayEvents> {
                         ExtendedMenuBuilder::new()
                             .cwec("Enabled", c.stadium.btt.enabled, TrayEvents::StadiumEnableBTT, &[c.stadium.enabled])
                             .cwec("Show stage name", c.stadium.btt.show_stage_name, TrayEvents::StadiumBTTShowStageName, &[c.stadium.enabled])
+                            .cwec("Show personal record", c.stadium.btt.show_record, TrayEvents::StadiumBTTShowRecord, &[c.stadium.enabled, c.stadium.btt.enabled])
+                            .cwec("Show time", c.stadium.btt.show_progress, TrayEvents::StadiumBTTShowProgress, &[c.stadium.enabled, c.stadium.btt.enabled])
                             .into()
                     )
                     .submenu(
                         "Multi-Man Melee",
                         ExtendedMenuBuilder::new()
                             .cwec("Enabled", c.stadium.mmm.enabled, TrayEvents::StadiumEnableMMM, &[c.stadium.enabled])
+                            .cwec("Show personal record", c.stadium.mmm.show_record, TrayEvents::StadiumMMMShowRecord, &[c.stadium.enabled, c.stadium.mmm.enabled])
+                            .cwec("Show time", c.stadium.mmm.show_progress, TrayEvents::StadiumMMMShowProgress, &[c.stadium.enabled, c.stadium.mmm.enabled])
+                            .into()
+                    )
+                    .into()
+        )
+        .submenu(
+            "1P Mode",
+            ExtendedMenuBuilder::new()
+                    .checkable("Enabled", c.one_player.enabled, TrayEvents::EnableOnePlayer)
+                    .submenu(
+                        "Classic",
+                        ExtendedMenuBuilder::new()
+                            .cwec("Enabled", c.one_player.classic.enabled, TrayEvents::OnePlayerEnableClassic, &[c.one_player.enabled])
+                            .into()
+                    )
+                    .submenu(
+                        "Adventure",
+                        ExtendedMenuBuilder::new()
+                            .cwec("Enabled", c.one_player.adventure.enabled, TrayEvents::OnePlayerEnableAdventure, &[c.one_player.enabled])
+                            .into()
+                    )
+                    .submenu(
+                        "All-Star",
+                        ExtendedMenuBuilder::new()
+                            .cwec("Enabled", c.one_player.all_star.enabled, TrayEvents::OnePlayerEnableAllStar, &[c.one_player.enabled])
+                            .into()
+                    )
+                    .submenu(
+                        "Event Match",
+                        ExtendedMenuBuilder::new()
+                            .cwec("Enabled", c.one_player.event_match.enabled, TrayEvents::OnePlayerEnableEventMatch, &[c.one_player.enabled])
                             .into()
                     )
                     .into()
@@ -193,7 +301,7 @@ fn build_menu(melee_connected: &Arc<AtomicBool>) -> MenuBuilder<TrayEvents> {
 }
 
 pub fn run_tray(mrx: Receiver<MeleeTrayEvent>) {
-    let melee_connected = Arc::new(AtomicBool::new(false));
+    let melee_state = Arc::new(Mutex::new(MeleeTrayState { status: MeleeTrayEvent::Disconnected, instances: Vec::new(), attached_pid: None }));
 
     let (s, r) = mpsc::channel::<TrayEvents>();
     let icon_raw = include_bytes!("../assets/icon.ico");
@@ -203,7 +311,7 @@ pub fn run_tray(mrx: Receiver<MeleeTrayEvent>) {
         .icon_from_buffer(icon_raw)
         .tooltip("Slippi Discord Integration")
         .menu(
-            build_menu(&melee_connected)
+            build_menu(&melee_state)
         )
         .build()
         .unwrap();
@@ -212,7 +320,7 @@ pub fn run_tray(mrx: Receiver<MeleeTrayEvent>) {
     let shared_should_end = should_end.clone();
     std::thread::spawn(move || {
         let mut update_menu = || {
-            tray_icon.set_menu(&build_menu(&melee_connected)).unwrap();
+            tray_icon.set_menu(&build_menu(&melee_state)).unwrap();
         };
         let mut toggle_handler = |modifier: fn(&mut AppConfig)| {
             CONFIG.with_mut(|c| { modifier(c); write_config(c); });
@@ -221,13 +329,32 @@ pub fn run_tray(mrx: Receiver<MeleeTrayEvent>) {
 
         loop {
             if let Ok(melee_ev) = mrx.try_recv() {
-                melee_connected.store(melee_ev == MeleeTrayEvent::Connected, atomic::Ordering::Relaxed);
+                {
+                    let mut state = melee_state.lock().unwrap();
+                    match melee_ev {
+                        MeleeTrayEvent::Instances(instances, attached_pid) => {
+                            state.instances = instances;
+                            state.attached_pid = attached_pid;
+                        },
+                        status => state.status = status
+                    }
+                }
                 toggle_handler(|_|{});
             }
             if let Ok(tray_ev) = r.try_recv() {
                 match tray_ev {
                     TrayEvents::ShowInGameCharacter => toggle_handler(|f| f.global.show_in_game_character = !f.global.show_in_game_character),
                     TrayEvents::ShowInGameTime => toggle_handler(|f| f.global.show_in_game_time = !f.global.show_in_game_time),
+                    TrayEvents::ShowSession => toggle_handler(|f| f.global.show_session = !f.global.show_session),
+                    TrayEvents::ShowStocks => toggle_handler(|f| f.global.show_stocks = !f.global.show_stocks),
+                    TrayEvents::ShowWinLoss => toggle_handler(|f| f.global.show_win_loss = !f.global.show_win_loss),
+
+                    TrayEvents::DolphinPreferNetplay => toggle_handler(|f| f.dolphin.selection = DolphinSelection::PreferNetplay),
+                    TrayEvents::DolphinPreferInGame => toggle_handler(|f| f.dolphin.selection = DolphinSelection::PreferInGame),
+                    TrayEvents::DolphinPin(path) => {
+                        CONFIG.with_mut(|c| c.dolphin.pinned_path = if c.dolphin.pinned_path.as_ref() == Some(&path) { None } else { Some(path) });
+                        toggle_handler(|_|{});
+                    },
         
                     TrayEvents::EnableSlippi => toggle_handler(|f| f.slippi.enabled = !f.slippi.enabled),
                     TrayEvents::SlippiShowQueueing => toggle_handler(|f| f.slippi.show_queueing = !f.slippi.show_queueing),
@@ -237,6 +364,7 @@ pub fn run_tray(mrx: Receiver<MeleeTrayEvent>) {
                     TrayEvents::SlippiRankedShowRank => toggle_handler(|f| f.slippi.ranked.show_rank = !f.slippi.ranked.show_rank),
                     TrayEvents::SlippiRankedShowViewRankedProfileButton => toggle_handler(|f| f.slippi.ranked.show_view_ranked_profile_button = !f.slippi.ranked.show_view_ranked_profile_button),
                     TrayEvents::SlippiRankedShowScore => toggle_handler(|f| f.slippi.ranked.show_score = !f.slippi.ranked.show_score),
+                    TrayEvents::SlippiRankedShowStageStriking => toggle_handler(|f| f.slippi.ranked.show_stage_striking = !f.slippi.ranked.show_stage_striking),
         
                     TrayEvents::SlippiEnableUnranked => toggle_handler(|f| f.slippi.unranked.enabled = !f.slippi.unranked.enabled),
         
@@ -247,17 +375,31 @@ pub fn run_tray(mrx: Receiver<MeleeTrayEvent>) {
                     TrayEvents::EnableUnclePunch => toggle_handler(|f| f.uncle_punch.enabled = !f.uncle_punch.enabled),
         
                     TrayEvents::EnableVsMode => toggle_handler(|f| f.vs_mode.enabled = !f.vs_mode.enabled),
+                    TrayEvents::VsModeShowRules => toggle_handler(|f| f.vs_mode.show_rules = !f.vs_mode.show_rules),
         
                     TrayEvents::EnableTrainingMode => toggle_handler(|f| f.training_mode.enabled = !f.training_mode.enabled),
+                    TrayEvents::TrainingModeShowPartner => toggle_handler(|f| f.training_mode.show_partner = !f.training_mode.show_partner),
 
                     TrayEvents::EnableStadium => toggle_handler(|f| f.stadium.enabled = !f.stadium.enabled),
 
                     TrayEvents::StadiumEnableHRC => toggle_handler(|f| f.stadium.hrc.enabled = !f.stadium.hrc.enabled),
+                    TrayEvents::StadiumHRCShowRecord => toggle_handler(|f| f.stadium.hrc.show_record = !f.stadium.hrc.show_record),
+                    TrayEvents::StadiumHRCShowProgress => toggle_handler(|f| f.stadium.hrc.show_progress = !f.stadium.hrc.show_progress),
 
                     TrayEvents::StadiumEnableBTT => toggle_handler(|f| f.stadium.btt.enabled = !f.stadium.btt.enabled),
                     TrayEvents::StadiumBTTShowStageName => toggle_handler(|f| f.stadium.btt.show_stage_name = !f.stadium.btt.show_stage_name),
+                    TrayEvents::StadiumBTTShowRecord => toggle_handler(|f| f.stadium.btt.show_record = !f.stadium.btt.show_record),
+                    TrayEvents::StadiumBTTShowProgress => toggle_handler(|f| f.stadium.btt.show_progress = !f.stadium.btt.show_progress),
 
                     TrayEvents::StadiumEnableMMM => toggle_handler(|f| f.stadium.mmm.enabled = !f.stadium.mmm.enabled),
+                    TrayEvents::StadiumMMMShowRecord => toggle_handler(|f| f.stadium.mmm.show_record = !f.stadium.mmm.show_record),
+                    TrayEvents::StadiumMMMShowProgress => toggle_handler(|f| f.stadium.mmm.show_progress = !f.stadium.mmm.show_progress),
+
+                    TrayEvents::EnableOnePlayer => toggle_handler(|f| f.one_player.enabled = !f.one_player.enabled),
+                    TrayEvents::OnePlayerEnableClassic => toggle_handler(|f| f.one_player.classic.enabled = !f.one_player.classic.enabled),
+                    TrayEvents::OnePlayerEnableAdventure => toggle_handler(|f| f.one_player.adventure.enabled = !f.one_player.adventure.enabled),
+                    TrayEvents::OnePlayerEnableAllStar => toggle_handler(|f| f.one_player.all_star.enabled = !f.one_player.all_star.enabled),
+                    TrayEvents::OnePlayerEnableEventMatch => toggle_handler(|f| f.one_player.event_match.enabled = !f.one_player.event_match.enabled),
         
                     TrayEvents::OpenConfig => {
                         if let Some(conf_file) = get_appdata_file(format!("{}/{}/app_config.prefs.json", APP_INFO.author, APP_INFO.name).as_str()) {
@@ -280,6 +422,9 @@ pub fn run_tray(mrx: Receiver<MeleeTrayEvent>) {
         if shared_should_end.load(atomic::Ordering::Relaxed) {
             break;
         }
+        #[cfg(not(windows))]
+        crate::util::sleep(50);
+        #[cfg(windows)]
         unsafe {
             let mut msg = MaybeUninit::uninit();
             let bret = PeekMessageA(msg.as_mut_ptr(), None, 0, 0, PM_REMOVE);